@@ -0,0 +1,197 @@
+//! The `--cached` results cache.
+//!
+//! Each distinct query (the parsed flags, pattern and search path, plus the
+//! working directory they're relative to) gets one file under
+//! `$XDG_CACHE_HOME/qro`. It holds the result blocks of the last run together
+//! with a fingerprint of the searched tree: the modification time and size of
+//! every entry the walk visits, and of the refs of any repo whose log is
+//! searched. A later run reuses the blocks only if the fingerprint is
+//! unchanged, so any added, removed or modified file busts the cache.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cli::Cli;
+use crate::git::discover_git_repos;
+use crate::output::Block;
+use crate::search::for_each_entry;
+
+/// Directory holding the cache files: `$XDG_CACHE_HOME/qro`, falling back to
+/// `~/.cache/qro`.
+fn cache_dir() -> Option<PathBuf> {
+    let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_home.join("qro"))
+}
+
+/// Hash a path's modification time and size into `hasher`. Missing paths
+/// hash as such, so creating one also changes the fingerprint.
+fn hash_metadata(path: &Path, hasher: &mut DefaultHasher) {
+    path.hash(hasher);
+    match fs::symlink_metadata(path) {
+        Ok(meta) => {
+            meta.modified().ok().hash(hasher);
+            meta.len().hash(hasher);
+        }
+        Err(_) => None::<()>.hash(hasher),
+    }
+}
+
+/// Fingerprint the tree a search with `cli` would look at.
+fn fingerprint(cli: &Cli, log_pass: bool) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    for_each_entry(cli, true, |path, _| {
+        hash_metadata(path, &mut hasher);
+        Ok(())
+    })?;
+    if log_pass {
+        // A linked worktree's `.git` is a file; its own mtime is the best we
+        // can do without asking git.
+        let git_dirs = discover_git_repos(&cli.path)
+            .into_iter()
+            .map(|repo| repo.join(".git"))
+            .chain(cli.git_dir.iter().cloned());
+        for git_dir in git_dirs {
+            hash_metadata(&git_dir, &mut hasher);
+            for name in ["HEAD", "packed-refs", "logs/HEAD", "refs/heads", "refs/tags"] {
+                hash_metadata(&git_dir.join(name), &mut hasher);
+            }
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// The cache file for one query, and the tree's fingerprint as of now.
+pub(crate) struct Cache {
+    file: PathBuf,
+    fingerprint: u64,
+}
+
+impl Cache {
+    /// Locate the cache file for `cli` and fingerprint the tree. Returns
+    /// `None` if there's nowhere to keep a cache.
+    pub(crate) fn open(cli: &Cli, log_pass: bool) -> io::Result<Option<Cache>> {
+        let Some(dir) = cache_dir() else {
+            return Ok(None);
+        };
+        let mut hasher = DefaultHasher::new();
+        format!("{cli:?}").hash(&mut hasher);
+        std::env::current_dir()?.hash(&mut hasher);
+        Ok(Some(Cache {
+            file: dir.join(format!("{:016x}", hasher.finish())),
+            fingerprint: fingerprint(cli, log_pass)?,
+        }))
+    }
+
+    /// The blocks stored by the last run of this query, if the tree hasn't
+    /// changed since.
+    pub(crate) fn load(&self) -> Option<Vec<Block>> {
+        let text = fs::read_to_string(&self.file).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != format!("{:016x}", self.fingerprint) {
+            return None;
+        }
+
+        let mut blocks: Vec<Block> = Vec::new();
+        for line in lines {
+            let fields: Vec<String> = line.split('\t').map(unescape).collect();
+            match fields.as_slice() {
+                [tag, is_log, inline_sep, key, heading, summary] if tag == "B" => {
+                    blocks.push(Block {
+                        key: key.clone(),
+                        is_log: is_log == "1",
+                        heading: heading.clone(),
+                        inline_sep: match inline_sep.as_str() {
+                            ":" => ":",
+                            " " => " ",
+                            _ => return None,
+                        },
+                        lines: Vec::new(),
+                        context_lines: BTreeSet::new(),
+                        summary: summary.clone(),
+                    });
+                }
+                [tag, detail] if tag == "L" => blocks.last_mut()?.lines.push(detail.clone()),
+                [tag, detail] if tag == "C" => {
+                    let block = blocks.last_mut()?;
+                    block.context_lines.insert(block.lines.len());
+                    block.lines.push(detail.clone());
+                }
+                _ => return None,
+            }
+        }
+        Some(blocks)
+    }
+
+    /// Replace the cached blocks for this query.
+    pub(crate) fn store(&self, blocks: &[Block]) -> io::Result<()> {
+        let mut text = format!("{:016x}\n", self.fingerprint);
+        for block in blocks {
+            let fields = [
+                if block.is_log { "1" } else { "0" },
+                block.inline_sep,
+                &block.key,
+                &block.heading,
+                &block.summary,
+            ];
+            text.push('B');
+            for field in fields {
+                text.push('\t');
+                text.push_str(&escape(field));
+            }
+            text.push('\n');
+            for (i, line) in block.lines.iter().enumerate() {
+                text.push_str(if block.context_lines.contains(&i) { "C\t" } else { "L\t" });
+                text.push_str(&escape(line));
+                text.push('\n');
+            }
+        }
+
+        // Write then rename, so a concurrent run never reads half a file.
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.file.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.file)
+    }
+}
+
+/// Escape the characters the cache file format uses as delimiters.
+fn escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
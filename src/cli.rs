@@ -1,11 +1,61 @@
-use clap::Parser;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
 use clap_complete::Shell;
 
+/// Order of git log blocks (`--sort-repos`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SortRepos {
+    /// By repo path, interleaved with the file results
+    #[default]
+    Path,
+    /// By repo directory name, after the file results
+    Name,
+    /// Most matching commits first, after the file results
+    Commits,
+}
+
+/// Which repeated matching lines `--dedup` drops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Dedup {
+    /// Lines identical to the match just before them in the same file
+    Consecutive,
+    /// Lines already matched earlier in the same file
+    File,
+    /// Lines already matched earlier in any file, in path order
+    All,
+}
+
+/// Where in the source `--in` requires a match to be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum InContext {
+    /// Inside comments
+    Comments,
+    /// Inside string literals
+    Strings,
+}
+
+/// Order in which result blocks are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SortBy {
+    /// By path, byte-wise
+    #[default]
+    Path,
+    /// Shallowest paths first, then by path
+    Depth,
+    /// By file name regardless of directory, then by path
+    Name,
+    /// Newest last commit first (one `git log` per matched file; uncommitted files last)
+    GitModified,
+}
+
 /// qro - Quick search combining ripgrep and fd
 ///
 /// Search both file contents and file names with a single command.
 #[derive(Parser, Debug)]
-#[command(name = "qro", version, disable_version_flag = true, about, after_help = "\
+#[command(name = "qro", version, disable_version_flag = true, about, args_override_self = true, after_help = "\
 Ignore files:\n  \
 qro respects .ignore files (same syntax as .gitignore) for excluding\n  \
 files and directories from search results. Place a .ignore file in any\n  \
@@ -13,24 +63,77 @@ directory; patterns apply to that directory and its children. This is\n  \
 independent of git — useful for excluding build artifacts, logs, etc.\n  \
 in non-git directories or without polluting .gitignore.\n\n  \
 Precedence (highest to lowest):\n    \
-1. Command-line flags (-x, -g, --no-ignore)\n    \
+1. Command-line flags (-x, -g, --no-ignore, --no-ignore-vcs, --no-ignore-dot)\n    \
 2. .ignore\n    \
 3. .gitignore\n    \
 4. .git/info/exclude\n    \
-5. Global gitignore")]
+5. Global gitignore\n    \
+6. --exclude-file files, later ones first\n    \
+7. qro global ignore ($XDG_CONFIG_HOME/qro/ignore, or ~/.config/qro/ignore)\n\n  \
+When -g and -x both match a file, -x wins: the file is excluded no\n  \
+matter which flag comes first on the command line.\n\n\
+Project config:\n  \
+A .qroconfig file in the search path or any parent directory supplies\n  \
+default flags, one argument per line (e.g. --type=rust); lines starting\n  \
+with # are comments. Flags on the command line take precedence, and\n  \
+configs nearer the search path beat those further up. Use --no-config\n  \
+to ignore them. Flags that read or write other files (--print-files-to,\n  \
+--git-log-arg, --git-dir, --exclude-file, --patterns-from, --cached) are\n  \
+only accepted on the command line.")]
 pub(crate) struct Cli {
     /// Search pattern (regex)
-    #[arg(required_unless_present = "completions")]
+    #[arg(required_unless_present_any = ["completions", "version_verbose", "patterns_from"])]
     pub pattern: Option<String>,
 
+    /// Read patterns from FILE (`-` for stdin), one per line or NUL-separated; any of them may match.
+    /// The only positional argument is then the search path
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["hex", "line_glob"])]
+    pub patterns_from: Option<PathBuf>,
+
+    /// Patterns read by [`Cli::load_patterns`].
+    #[arg(skip)]
+    pub patterns: Vec<String>,
+
     /// Directory to search (defaults to current directory)
-    #[arg(default_value = ".")]
+    // With --patterns-from, the path is given in the pattern's place.
+    #[arg(default_value = ".", conflicts_with = "patterns_from")]
     pub path: String,
 
     /// Only search file names
     #[arg(short = 'n', long)]
     pub names_only: bool,
 
+    /// Only report name matches that have extended attribute NAME, optionally with VALUE
+    #[arg(long, value_name = "NAME[=VALUE]", value_parser = parse_xattr)]
+    pub xattr: Option<(String, Option<String>)>,
+
+    /// Under name matches without content matches, show the file's first matching line (or first line)
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Only search files inside git repos: the search path's own, or its immediate children
+    #[arg(long)]
+    pub repos_only: bool,
+
+    /// Match directory names too, shown with a trailing slash
+    #[arg(long)]
+    pub include_dirs: bool,
+
+    /// Match name patterns against paths with the platform's separator; by default paths use `/`
+    #[arg(long)]
+    pub native_separators: bool,
+
+    /// Match file names against their absolute canonical path (one extra syscall per file)
+    #[arg(long)]
+    pub canonicalize: bool,
+
+    /// Only list files whose name matches but whose contents don't
+    #[arg(
+        long,
+        conflicts_with_all = ["names_only", "content_only", "log_only", "hex", "in_context", "count_lines_total"]
+    )]
+    pub name_only_unmatched: bool,
+
     /// Only search file contents
     #[arg(short = 'c', long)]
     pub content_only: bool,
@@ -39,18 +142,70 @@ pub(crate) struct Cli {
     #[arg(short, long)]
     pub ignore_case: bool,
 
+    /// Case rule for file-name matching only; overrides -i (e.g. =false)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub name_ignore_case: Option<bool>,
+
+    /// Case rule for content matching only; overrides -i (e.g. =false)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub content_ignore_case: Option<bool>,
+
     /// Include hidden files
     #[arg(long)]
     pub hidden: bool,
 
+    /// Only search hidden files and files inside hidden directories
+    #[arg(long)]
+    pub hidden_only: bool,
+
+    /// Only search zero-byte files, e.g. placeholders and marker files
+    #[arg(long, conflicts_with = "skip_empty")]
+    pub only_empty: bool,
+
+    /// Skip zero-byte files
+    #[arg(long)]
+    pub skip_empty: bool,
+
     /// Don't respect .gitignore
     #[arg(long)]
     pub no_ignore: bool,
 
+    /// Don't respect .gitignore, .git/info/exclude or the global gitignore, but keep .ignore
+    #[arg(long)]
+    pub no_ignore_vcs: bool,
+
+    /// Don't respect .ignore files, but keep the git ignore rules
+    #[arg(long)]
+    pub no_ignore_dot: bool,
+
+    /// Don't respect qro's global ignore file ($XDG_CONFIG_HOME/qro/ignore)
+    #[arg(long)]
+    pub no_ignore_global_qro: bool,
+
+    /// Skip what FILE ignores, using .gitignore syntax including `!` (repeatable; applies even with --no-ignore)
+    #[arg(long, action = clap::ArgAction::Append, value_name = "FILE")]
+    pub exclude_file: Vec<PathBuf>,
+
     /// Filter by file type (e.g., rust, python)
     #[arg(short = 't', long = "type")]
     pub file_type: Option<String>,
 
+    /// With -t, also match extensionless files by their #! line (reads each one)
+    #[arg(long, requires = "file_type")]
+    pub type_shebang: bool,
+
     /// Treat pattern as a literal string, not a regex
     #[arg(short = 'F', long)]
     pub fixed_strings: bool,
@@ -59,20 +214,92 @@ pub(crate) struct Cli {
     #[arg(short = 'g', long)]
     pub glob: Option<String>,
 
+    /// Only search files whose file name matches REGEX (e.g., --name '_test')
+    #[arg(long, value_name = "REGEX")]
+    pub name: Option<String>,
+
     /// Exclude files matching glob pattern (repeatable)
     #[arg(short = 'x', long = "ignore", action = clap::ArgAction::Append)]
     pub exclude: Vec<String>,
 
+    /// Don't descend into directories with this name, e.g. node_modules (repeatable)
+    #[arg(
+        long,
+        visible_alias = "exclude-dir",
+        action = clap::ArgAction::Append,
+        value_name = "DIRNAME"
+    )]
+    pub stop_at: Vec<String>,
+
+    /// Treat PATTERN as hex bytes (e.g. deadbeef) and report byte offsets in file contents
+    #[arg(
+        long,
+        conflicts_with_all = ["names_only", "log_only", "fixed_strings", "word_regexp", "slurp"]
+    )]
+    pub hex: bool,
+
     /// Only match whole words
     #[arg(short = 'w', long)]
     pub word_regexp: bool,
 
+    /// Match content lines against the pattern as a glob over the whole line, e.g. `*foo*bar`
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "names_only", "log_only", "fixed_strings", "word_regexp", "hex", "slurp",
+            "binary_hex", "in_context", "dont_match", "filter_out", "show_match_count",
+        ]
+    )]
+    pub line_glob: bool,
+
+    /// With -w, use ASCII word boundaries (faster; non-ASCII letters aren't word characters)
+    #[arg(long)]
+    pub no_unicode_boundaries: bool,
+
     /// Disable git log search [git log is searched by default]
-    #[arg(long = "no-log")]
+    #[arg(long = "no-log", overrides_with = "log_only")]
     pub no_log: bool,
 
-    /// Only search git logs
-    #[arg(short = 'l', long)]
+    /// Search commit diffs as well as messages in git log (like `git log -G`)
+    #[arg(long)]
+    pub log_everything: bool,
+
+    /// Also search HEAD's reflog entries in git log (like `git reflog`)
+    #[arg(long)]
+    pub log_reflog: bool,
+
+    /// Also search stash messages in git log (like `git stash list`)
+    #[arg(long)]
+    pub log_stash: bool,
+
+    /// Only search git logs of repos whose path matches this glob (repeatable)
+    #[arg(long, action = clap::ArgAction::Append, value_name = "GLOB")]
+    pub log_repo_glob: Vec<String>,
+
+    /// Search the git logs of at most N repos; 0 for no limit
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    pub max_git_log_repos: usize,
+
+    /// Pass ARG through to `git log`, e.g. --first-parent (repeatable)
+    #[arg(long, action = clap::ArgAction::Append, value_name = "ARG", allow_hyphen_values = true)]
+    pub git_log_arg: Vec<String>,
+
+    /// Also search the log of the git directory PATH, e.g. a bare repo or mirror (repeatable)
+    #[arg(long, action = clap::ArgAction::Append, value_name = "PATH")]
+    pub git_dir: Vec<PathBuf>,
+
+    /// Fail if no git repository is found for the log search
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "no_log", "hex", "line_glob", "in_context", "count", "count_matches",
+            "count_lines_total", "count_by_dir", "json", "name_only_unmatched", "find_duplicates",
+        ]
+    )]
+    pub require_git: bool,
+
+    /// Only search git logs (--no-log and -l override each other; the last one wins)
+    #[arg(short = 'l', long, overrides_with = "no_log")]
     pub log_only: bool,
 
     /// Exclude matches that are part of a larger match of PATTERN (repeatable)
@@ -83,6 +310,236 @@ pub(crate) struct Cli {
     #[arg(short = 'V', long, action = clap::ArgAction::Append, value_name = "PATTERN")]
     pub filter_out: Vec<String>,
 
+    /// Match each file as one buffer so patterns can span lines (reads whole files into memory)
+    #[arg(long)]
+    pub slurp: bool,
+
+    /// Show a hex dump around each match in binary files instead of `(binary file matches)`
+    #[arg(long, conflicts_with_all = ["hex", "slurp"])]
+    pub binary_hex: bool,
+
+    /// Only match inside comments or strings (Rust, Python, C, C++, Java, JS, TS and Go files)
+    #[arg(long = "in", value_enum, value_name = "CONTEXT", conflicts_with_all = ["hex", "slurp"])]
+    pub in_context: Option<InContext>,
+
+    /// Skip the contents of files with any line longer than N bytes, e.g. minified code
+    #[arg(long, value_name = "N")]
+    pub max_line_length: Option<u64>,
+
+    /// Show at most N matching lines per file, noting how many more there are
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_results_per_file: Option<u32>,
+
+    /// Show N lines after each matching line
+    #[arg(
+        short = 'A',
+        long,
+        value_name = "N",
+        conflicts_with_all = ["hex", "slurp", "line_glob"]
+    )]
+    pub after_context: Option<u32>,
+
+    /// Show N lines before each matching line
+    #[arg(
+        short = 'B',
+        long,
+        value_name = "N",
+        conflicts_with_all = ["hex", "slurp", "line_glob"]
+    )]
+    pub before_context: Option<u32>,
+
+    /// Show N lines before and after each matching line; -A and -B take precedence
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "N",
+        conflicts_with_all = ["hex", "slurp", "line_glob"]
+    )]
+    pub context: Option<u32>,
+
+    /// Strip trailing spaces and tabs from matched lines in the output
+    #[arg(long)]
+    pub trim_trailing: bool,
+
+    /// List each file's content matches last line first
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Drop repeated matching lines, keeping the first [default: file]
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SCOPE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "file"
+    )]
+    pub dedup: Option<Dedup>,
+
+    /// Only match content within lines START:END (1-based, inclusive; either side may be omitted)
+    #[arg(long, value_name = "START:END", value_parser = parse_line_range)]
+    pub line_range: Option<(u64, u64)>,
+
+    /// Sort results by this key
+    #[arg(long, value_enum, default_value_t = SortBy::Path, value_name = "KEY")]
+    pub sort: SortBy,
+
+    /// Order of git log blocks
+    #[arg(long, value_enum, default_value_t = SortRepos::Path, value_name = "ORDER")]
+    pub sort_repos: SortRepos,
+
+    /// Remove carriage returns from matched lines [default]
+    #[arg(long, overrides_with = "keep_cr")]
+    pub strip_cr: bool,
+
+    /// Show matched lines with their carriage returns, as stored
+    #[arg(long, overrides_with = "strip_cr")]
+    pub keep_cr: bool,
+
+    /// Show line numbers on matched lines [default]
+    #[arg(long, overrides_with = "no_line_number")]
+    pub line_number: bool,
+
+    /// Don't show line numbers, only the matched lines
+    #[arg(short = 'N', long, overrides_with = "line_number")]
+    pub no_line_number: bool,
+
+    /// Print the file path above its matches [default]
+    #[arg(long, overrides_with = "no_heading")]
+    pub heading: bool,
+
+    /// Print the file path on every match line instead of above them
+    #[arg(long, overrides_with = "heading")]
+    pub no_heading: bool,
+
+    /// Don't print blank lines between result blocks
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Indent the lines under each heading by N spaces
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub indent: usize,
+
+    /// Print STR between result blocks instead of a blank line
+    #[arg(long, value_name = "STR", overrides_with = "no_group_separator")]
+    pub group_separator: Option<String>,
+
+    /// Print nothing between result blocks (same as --compact)
+    #[arg(long, overrides_with = "group_separator")]
+    pub no_group_separator: bool,
+
+    /// Print one summary line per file, e.g. `path (3 content, name match)`
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Print content matches as ripgrep-style JSON Lines (`begin`, `match`, `context`, `end`);
+    /// name and git log matches are left out, and carriage returns are kept
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "names_only", "log_only", "hex", "slurp", "line_glob", "binary_hex", "summary",
+            "count_lines_total", "count_by_dir", "find_duplicates", "name_only_unmatched", "cached",
+            "stats",
+        ]
+    )]
+    pub json: bool,
+
+    /// Follow each matching line with the number of matches on it, e.g. `(3 matches)`
+    #[arg(long, conflicts_with = "hex")]
+    pub show_match_count: bool,
+
+    /// File encoding, e.g. latin1 or utf-16le (`auto`: latin1 for files that aren't UTF-8)
+    #[arg(long, value_name = "ENCODING", conflicts_with = "slurp")]
+    pub encoding: Option<String>,
+
+    /// Also write the matched file paths, NUL-separated, to PATH (e.g. /dev/fd/3)
+    #[arg(long, value_name = "PATH")]
+    pub print_files_to: Option<PathBuf>,
+
+    /// Print the number of matching lines in each file with any, as `path:N`
+    #[arg(
+        long,
+        overrides_with = "count_matches",
+        conflicts_with_all = [
+            "names_only", "log_only", "hex", "count_lines_total", "count_by_dir", "json",
+            "find_duplicates", "name_only_unmatched", "cached",
+        ]
+    )]
+    pub count: bool,
+
+    /// Like --count, but count every match, including several on one line
+    #[arg(
+        long,
+        overrides_with = "count",
+        conflicts_with_all = [
+            "names_only", "log_only", "hex", "slurp", "line_glob", "count_lines_total",
+            "count_by_dir", "json", "find_duplicates", "name_only_unmatched", "cached",
+        ]
+    )]
+    pub count_matches: bool,
+
+    /// Print only the total number of matching lines across all files
+    #[arg(long, conflicts_with_all = ["names_only", "log_only"])]
+    pub count_lines_total: bool,
+
+    /// Print the number of matching lines under each directory DEPTH levels below the search path [default: 1]
+    #[arg(
+        long,
+        value_name = "DEPTH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["names_only", "log_only", "count_lines_total", "cached"]
+    )]
+    pub count_by_dir: Option<u32>,
+
+    /// Stop with an error at the first file or directory that can't be read, instead of skipping it
+    #[arg(long)]
+    pub fail_fast_on_error: bool,
+
+    /// Instead of the matches, list groups of matched files with identical contents
+    #[arg(long, conflicts_with_all = ["log_only", "count_lines_total", "count_by_dir", "cached"])]
+    pub find_duplicates: bool,
+
+    /// Print match counts and time spent per phase after the results
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Reprint the last results of this same search if nothing in the tree has changed since
+    #[arg(long, conflicts_with_all = ["stats", "count_lines_total"])]
+    pub cached: bool,
+
+    /// Print MARKER when nothing matched [default marker: "(no matches)"]
+    #[arg(
+        long,
+        value_name = "MARKER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "(no matches)"
+    )]
+    pub print_empty: Option<String>,
+
+    /// Print nothing; exit 0 if anything matched, 1 otherwise
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Expand $VAR and ${VAR} in PATH, -g and -x (unset variables are left as-is)
+    #[arg(long)]
+    pub expand_env: bool,
+
+    /// Ignore .qroconfig files
+    #[arg(long)]
+    pub no_config: bool,
+
+    /// Run the search N times without printing results and report timings
+    #[arg(long, hide = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub benchmark: Option<u32>,
+
+    /// Search file contents on N threads; 0 picks a number from the available CPUs
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 0)]
+    pub threads: usize,
+
     /// Show detailed output
     #[arg(short, long)]
     pub verbose: bool,
@@ -91,6 +548,10 @@ pub(crate) struct Cli {
     #[arg(long, action = clap::ArgAction::Version)]
     pub version: (),
 
+    /// Print version with build and environment details for bug reports
+    #[arg(long)]
+    pub version_verbose: bool,
+
     /// Generate shell completions and exit
     #[arg(long, value_name = "SHELL")]
     pub completions: Option<Shell>,
@@ -98,8 +559,172 @@ pub(crate) struct Cli {
 
 impl Cli {
     /// Whether git log search should be performed.
-    /// On by default; --no-log disables.
+    /// On by default; --no-log disables. --no-log and --log-only override
+    /// each other, so at most one of them is ever set.
     pub fn wants_log(&self) -> bool {
         !self.no_log
     }
+
+    /// Whether results are printed; not with --quiet or --benchmark.
+    pub fn prints_results(&self) -> bool {
+        !self.quiet && self.benchmark.is_none()
+    }
+
+    /// Context lines to show before each content match (`-B`, else `-C`).
+    pub fn lines_before(&self) -> u64 {
+        self.before_context.or(self.context).unwrap_or(0).into()
+    }
+
+    /// Context lines to show after each content match (`-A`, else `-C`).
+    pub fn lines_after(&self) -> u64 {
+        self.after_context.or(self.context).unwrap_or(0).into()
+    }
+
+    /// Whether the file-name pass ignores case.
+    pub fn names_ignore_case(&self) -> bool {
+        self.name_ignore_case.unwrap_or(self.ignore_case)
+    }
+
+    /// Whether the content pass ignores case.
+    pub fn contents_ignore_case(&self) -> bool {
+        self.content_ignore_case.unwrap_or(self.ignore_case)
+    }
+
+    /// Read the `--patterns-from` patterns. Without a pattern argument, the
+    /// path lands in `pattern`, so it's moved back.
+    pub fn load_patterns(&mut self) -> io::Result<()> {
+        let Some(ref source) = self.patterns_from else {
+            return Ok(());
+        };
+        let text = if source.as_os_str() == "-" {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            fs::read_to_string(source)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", source.display())))?
+        };
+        let patterns: Vec<&str> = if text.contains('\0') {
+            text.split('\0').collect()
+        } else {
+            text.lines().collect()
+        };
+        self.patterns = patterns
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+        if self.patterns.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--patterns-from {}: no patterns", source.display()),
+            ));
+        }
+
+        if let Some(path) = self.pattern.take() {
+            self.path = path;
+        }
+        Ok(())
+    }
+
+    /// Expand environment variables in the path and glob arguments.
+    pub fn expand_env_vars(&mut self) {
+        self.path = expand_env(&self.path);
+        self.glob = self.glob.as_deref().map(expand_env);
+        for pattern in &mut self.exclude {
+            *pattern = expand_env(pattern);
+        }
+    }
+}
+
+/// Expand `$VAR` and `${VAR}` references. Unset variables, and `$` not
+/// followed by a name, are left untouched.
+fn expand_env(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(dollar) = rest.find('$') {
+        result.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(close) => (&braced[..close], close + 2),
+                None => ("", 0),
+            }
+        } else {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..len], len)
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => result.push_str(&value),
+            _ => result.push_str(&rest[dollar..dollar + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Parse a `START:END` line range. An omitted START means the first line,
+/// an omitted END means the last.
+fn parse_line_range(s: &str) -> Result<(u64, u64), String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got '{s}'"))?;
+    let parse = |part: &str, default: u64| -> Result<u64, String> {
+        if part.is_empty() {
+            Ok(default)
+        } else {
+            part.parse()
+                .map_err(|_| format!("invalid line number '{part}'"))
+        }
+    };
+    let start = parse(start, 1)?;
+    let end = parse(end, u64::MAX)?;
+    if start == 0 {
+        return Err("line numbers start at 1".to_string());
+    }
+    if start > end {
+        return Err(format!("start line {start} is after end line {end}"));
+    }
+    Ok((start, end))
+}
+
+/// Parse `--xattr NAME[=VALUE]`.
+fn parse_xattr(s: &str) -> Result<(String, Option<String>), String> {
+    let (name, value) = match s.split_once('=') {
+        Some((name, value)) => (name, Some(value.to_string())),
+        None => (s, None),
+    };
+    if name.is_empty() {
+        return Err("attribute name is empty".to_string());
+    }
+    Ok((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_env_plain_and_braced() {
+        // HOME is set in any environment the tests run in.
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_env("$HOME/src"), format!("{home}/src"));
+        assert_eq!(expand_env("${HOME}src"), format!("{home}src"));
+    }
+
+    #[test]
+    fn expand_env_leaves_unset_and_bare_dollars() {
+        assert_eq!(expand_env("$QRO_UNSET_VAR/x"), "$QRO_UNSET_VAR/x");
+        assert_eq!(expand_env("${QRO_UNSET_VAR}"), "${QRO_UNSET_VAR}");
+        assert_eq!(expand_env("cost$"), "cost$");
+        assert_eq!(expand_env("a$-b"), "a$-b");
+        assert_eq!(expand_env("${unclosed"), "${unclosed");
+    }
 }
@@ -0,0 +1,68 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE_NAME: &str = ".qroconfig";
+
+/// Flags that make qro (or git) read or write files other than the ones
+/// searched. A config file comes with whatever tree is being searched, such
+/// as a freshly cloned repo, so it may not use them.
+const FORBIDDEN_FLAGS: &[&str] = &[
+    "--print-files-to",
+    "--git-log-arg",
+    "--git-dir",
+    "--exclude-file",
+    "--patterns-from",
+    "--cached",
+];
+
+/// Find `.qroconfig` files in the search root and its ancestors, outermost
+/// first so that configs closer to the search root take precedence.
+fn find_config_files(search_path: &str) -> Vec<PathBuf> {
+    let Ok(root) = Path::new(search_path).canonicalize() else {
+        return Vec::new();
+    };
+    let start = if root.is_dir() {
+        root.as_path()
+    } else {
+        root.parent().unwrap_or(&root)
+    };
+    let mut files: Vec<PathBuf> = start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .filter(|path| path.is_file())
+        .collect();
+    files.reverse();
+    files
+}
+
+/// Read default arguments from every `.qroconfig` that applies to the search
+/// path. Each line is one argument (e.g. `--type=rust` or `--hidden`); blank
+/// lines and lines starting with `#` are skipped. Any of the
+/// [`FORBIDDEN_FLAGS`] is an error.
+pub(crate) fn config_args(search_path: &str) -> io::Result<Vec<OsString>> {
+    let mut args = Vec::new();
+    for file in find_config_files(search_path) {
+        let contents = fs::read_to_string(&file)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", file.display())))?;
+        for line in contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let flag = line.split('=').next().unwrap_or(line);
+            if FORBIDDEN_FLAGS.contains(&flag) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{}: {flag} can only be given on the command line",
+                        file.display()
+                    ),
+                ));
+            }
+            args.push(OsString::from(line));
+        }
+    }
+    Ok(args)
+}
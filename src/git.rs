@@ -1,22 +1,41 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use globset::{Glob, GlobSetBuilder};
+
 use crate::cli::Cli;
-use crate::search::prepare_regex_pattern;
+use crate::search::prepare_git_pattern;
+
+/// Which part of a commit matched the pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogMatchKind {
+    /// The commit message.
+    Message,
+    /// Lines added or removed by the commit (`git log -G`).
+    Diff,
+    /// Both the message and the diff.
+    Both,
+    /// A HEAD reflog entry (`--log-reflog`); the message is the entry's.
+    Reflog,
+    /// A stash entry (`--log-stash`); the message is the stash's.
+    Stash,
+}
 
 pub(crate) struct GitLogMatch {
     pub repo: String,
     pub hash: String,
     pub date: String,
     pub message: String,
+    pub kind: LogMatchKind,
 }
 
 /// Discover git repositories relevant to the search path.
 ///
 /// 1. If the search path is inside a git repo, include that repo.
 /// 2. Check immediate children of the search path for .git directories.
+///
 /// Deduplicate by canonical path.
 pub(crate) fn discover_git_repos(search_path: &str) -> Vec<PathBuf> {
     let mut repos = Vec::new();
@@ -26,28 +45,29 @@ pub(crate) fn discover_git_repos(search_path: &str) -> Vec<PathBuf> {
     if let Ok(output) = Command::new("git")
         .args(["-C", search_path, "rev-parse", "--show-toplevel"])
         .output()
+        && output.status.success()
     {
-        if output.status.success() {
-            let toplevel = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let path = PathBuf::from(&toplevel);
-            if let Ok(canonical) = path.canonicalize() {
-                if seen.insert(canonical) {
-                    repos.push(path);
-                }
-            }
+        let toplevel = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let path = PathBuf::from(&toplevel);
+        if let Ok(canonical) = path.canonicalize()
+            && seen.insert(canonical)
+        {
+            repos.push(path);
         }
     }
 
-    // Step 2: Check immediate children for .git directories.
+    // Step 2: Check immediate children for .git directories, in name order
+    // so that --max-git-log-repos always keeps the same ones.
     if let Ok(entries) = std::fs::read_dir(search_path) {
-        for entry in entries.flatten() {
-            let child = entry.path();
-            if child.is_dir() && child.join(".git").exists() {
-                if let Ok(canonical) = child.canonicalize() {
-                    if seen.insert(canonical) {
-                        repos.push(child);
-                    }
-                }
+        let mut children: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+        children.sort();
+        for child in children {
+            if child.is_dir()
+                && child.join(".git").exists()
+                && let Ok(canonical) = child.canonicalize()
+                && seen.insert(canonical)
+            {
+                repos.push(child);
             }
         }
     }
@@ -55,6 +75,90 @@ pub(crate) fn discover_git_repos(search_path: &str) -> Vec<PathBuf> {
     repos
 }
 
+/// The time of the last commit touching each of `files` (`--sort
+/// git-modified`), keyed by the path as given. Files outside the repos found
+/// by [`discover_git_repos`], or never committed, are left out.
+///
+/// That's a `git log -1` per file, so it's only worth running on the files
+/// that matched, and it's still slow when many did.
+pub(crate) fn last_commit_times<'a>(
+    search_path: &str,
+    files: impl Iterator<Item = &'a str>,
+) -> HashMap<String, i64> {
+    let repos: Vec<PathBuf> = discover_git_repos(search_path)
+        .into_iter()
+        .filter_map(|repo| repo.canonicalize().ok())
+        .collect();
+    let mut times = HashMap::new();
+    for file in files {
+        let Ok(path) = Path::new(file).canonicalize() else {
+            continue;
+        };
+        // The innermost repo holding the file.
+        let Some(repo) = repos
+            .iter()
+            .filter(|repo| path.starts_with(repo))
+            .max_by_key(|repo| repo.as_os_str().len())
+        else {
+            continue;
+        };
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["log", "-1", "--format=%ct", "--"])
+            .arg(&path)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                if let Ok(time) = String::from_utf8_lossy(&output.stdout).trim().parse() {
+                    times.insert(file.to_string(), time);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("qro: git log in {}: {e}", repo.display());
+                break;
+            }
+        }
+    }
+    times
+}
+
+/// A repo whose log is searched.
+struct LogRepo {
+    path: PathBuf,
+    /// Whether `path` is the git directory itself (`--git-dir`), as for a
+    /// bare repo, rather than a working tree.
+    git_dir: bool,
+}
+
+impl LogRepo {
+    /// The `git` options that point it at this repo.
+    fn location_args(&self) -> [&std::ffi::OsStr; 2] {
+        let option = if self.git_dir { "--git-dir" } else { "-C" };
+        [option.as_ref(), self.path.as_os_str()]
+    }
+}
+
+/// Check that a `--git-dir` path is a git directory, so a typo is reported
+/// rather than matching nothing.
+fn check_git_dir(path: &Path) -> io::Result<()> {
+    let is_git_dir = Command::new("git")
+        .arg("--git-dir")
+        .arg(path)
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if is_git_dir {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--git-dir {}: not a git repository", path.display()),
+        ))
+    }
+}
+
 /// Filter git log matches using dont_match and filter_out patterns.
 pub(crate) fn filter_git_log_matches(
     matches: Vec<GitLogMatch>,
@@ -70,8 +174,9 @@ pub(crate) fn filter_git_log_matches(
                 return false;
             }
 
-            // dont_match: keep only if at least one search match is independent
-            if !dont_match.is_empty() {
+            // dont_match: keep only if at least one search match is independent.
+            // Diff-only hits needn't mention the pattern in the message at all.
+            if !dont_match.is_empty() && m.kind != LogMatchKind::Diff {
                 let search_matches: Vec<(usize, usize)> = search_re
                     .find_iter(&m.message)
                     .map(|mat| (mat.start(), mat.end()))
@@ -103,63 +208,269 @@ pub(crate) fn filter_git_log_matches(
         .collect()
 }
 
+/// Keep only repos whose path matches one of the `--log-repo-glob` patterns.
+/// With no patterns, every repo is kept.
+fn filter_repos(repos: Vec<LogRepo>, globs: &[String]) -> io::Result<Vec<LogRepo>> {
+    if globs.is_empty() {
+        return Ok(repos);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?);
+    }
+    let set = builder
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    Ok(repos
+        .into_iter()
+        .filter(|repo| set.is_match(&repo.path))
+        .collect())
+}
+
+/// Parse `git log --format='%h %ct %ad %s'` output (or `%gs` for reflog
+/// walks) into `(timestamp, match)` pairs.
+///
+/// Every line must be such a record. Anything else means a `--git-log-arg`
+/// option added to the output (a diff, a stat, a graph), and is an error
+/// rather than a bogus match.
+fn parse_log_output(
+    stdout: &str,
+    repo: &str,
+    kind: LogMatchKind,
+) -> io::Result<Vec<(i64, GitLogMatch)>> {
+    let record = |line: &str| {
+        let mut parts = line.splitn(4, ' ');
+        let (hash, timestamp, date, message) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        let is_hash = !hash.is_empty() && hash.bytes().all(|b| b.is_ascii_hexdigit());
+        let is_date = date.len() == 10 && date.bytes().all(|b| b.is_ascii_digit() || b == b'-');
+        if !is_hash || !is_date {
+            return None;
+        }
+        Some((
+            timestamp.parse().ok()?,
+            GitLogMatch {
+                repo: repo.to_string(),
+                hash: hash.to_string(),
+                date: date.to_string(),
+                message: message.to_string(),
+                kind,
+            },
+        ))
+    };
+    stdout
+        .lines()
+        .map(|line| {
+            record(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "git log in {repo}: unexpected output line {line:?}; \
+                         a --git-log-arg option may have changed the output"
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+/// `git log` options qro sets itself; passing them again through
+/// `--git-log-arg` would break output parsing or change what matches.
+const RESERVED_LOG_ARGS: &[&str] = &[
+    "--format",
+    "--pretty",
+    "--oneline",
+    "--date",
+    "--grep",
+    "-G",
+    "-S",
+    "-E",
+    "--extended-regexp",
+    "-i",
+    "--regexp-ignore-case",
+    "--all-match",
+    "--invert-grep",
+];
+
+/// `git log` options that add to or redirect its output, so qro would read
+/// the wrong lines or nothing at all. The common ones are refused up front;
+/// any others fail in [`parse_log_output`].
+const OUTPUT_LOG_ARGS: &[&str] = &[
+    "--output",
+    "--name-only",
+    "--name-status",
+    "--stat",
+    "--numstat",
+    "--shortstat",
+    "--raw",
+    "-p",
+    "-u",
+    "--patch",
+    "--graph",
+    "-z",
+];
+
+fn check_log_args(args: &[String]) -> io::Result<()> {
+    // Long options may carry `=value`; short ones may have it attached.
+    let is_any_of = |arg: &str, options: &[&str]| {
+        options.iter().any(|r| match arg.strip_prefix(r) {
+            Some(rest) => rest.is_empty() || rest.starts_with('=') || !r.starts_with("--"),
+            None => false,
+        })
+    };
+    for arg in args {
+        let problem = if is_any_of(arg, RESERVED_LOG_ARGS) {
+            "qro sets this git log option itself"
+        } else if is_any_of(arg, OUTPUT_LOG_ARGS) {
+            "this git log option changes the output qro reads"
+        } else {
+            continue;
+        };
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--git-log-arg {arg}: {problem}"),
+        ));
+    }
+    Ok(())
+}
+
 pub(crate) fn search_git_log(cli: &Cli) -> io::Result<Vec<GitLogMatch>> {
-    let repos = discover_git_repos(&cli.path);
+    check_log_args(&cli.git_log_arg)?;
+    for git_dir in &cli.git_dir {
+        check_git_dir(git_dir)?;
+    }
+    let repos: Vec<LogRepo> = discover_git_repos(&cli.path)
+        .into_iter()
+        .map(|path| LogRepo { path, git_dir: false })
+        .chain(cli.git_dir.iter().map(|path| LogRepo {
+            path: path.clone(),
+            git_dir: true,
+        }))
+        .collect();
+    if repos.is_empty() && cli.require_git {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: not in a git repository, and no child repositories found", cli.path),
+        ));
+    }
+    let mut repos = filter_repos(repos, &cli.log_repo_glob)?;
+    if cli.max_git_log_repos > 0 && repos.len() > cli.max_git_log_repos {
+        eprintln!(
+            "qro: found {} git repositories, only searching the first {} (see --max-git-log-repos)",
+            repos.len(),
+            cli.max_git_log_repos
+        );
+        repos.truncate(cli.max_git_log_repos);
+    }
     let mut matches = Vec::new();
-    let pattern = prepare_regex_pattern(cli);
+    let pattern = prepare_git_pattern(cli);
+
+    // Commit filters to run per repo: messages always, diffs with
+    // --log-everything, and reflog walks with --log-reflog and --log-stash.
+    let mut filters = vec![(
+        LogMatchKind::Message,
+        vec!["--grep".to_string(), pattern.clone()],
+    )];
+    if cli.log_everything {
+        filters.push((LogMatchKind::Diff, vec![format!("-G{pattern}")]));
+    }
+    if cli.log_reflog {
+        filters.push((
+            LogMatchKind::Reflog,
+            vec!["-g".to_string(), format!("--grep-reflog={pattern}")],
+        ));
+    }
+    if cli.log_stash {
+        // What `git stash list` shows. Fails harmlessly if there's no stash.
+        filters.push((
+            LogMatchKind::Stash,
+            vec![
+                "-g".to_string(),
+                "--first-parent".to_string(),
+                format!("--grep-reflog={pattern}"),
+                "refs/stash".to_string(),
+            ],
+        ));
+    }
+
+    // With --quiet one commit answers the question, unless it could still be
+    // filtered out afterwards.
+    let first_only = cli.quiet && cli.dont_match.is_empty() && cli.filter_out.is_empty();
 
     for repo in repos {
-        let repo_str = repo.to_string_lossy().to_string();
-        let mut cmd = Command::new("git");
-        cmd.args([
-            "-C",
-            &repo_str,
-            "log",
-            "--format=%h %ad %s",
-            "--date=short",
-            "-E",
-        ]);
-        if cli.ignore_case {
-            cmd.arg("-i");
-        }
-        cmd.args(["--grep", &pattern]);
-
-        let output = match cmd.output() {
-            Ok(output) => output,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                if cli.log_only {
-                    return Err(io::Error::new(
-                        io::ErrorKind::NotFound,
-                        "git is not installed",
-                    ));
-                }
-                eprintln!("qro: git not found, skipping log search");
-                return Ok(matches);
+        let repo_str = repo.path.to_string_lossy().to_string();
+        let mut repo_matches: Vec<(i64, GitLogMatch)> = Vec::new();
+        // Index in `repo_matches` of each commit listed so far.
+        let mut commit_index: HashMap<String, usize> = HashMap::new();
+
+        for (kind, filter) in &filters {
+            // Reflog walks report the entry's own message rather than the commit's.
+            let format = match kind {
+                LogMatchKind::Reflog | LogMatchKind::Stash => "--format=%h %ct %ad %gs",
+                _ => "--format=%h %ct %ad %s",
+            };
+            let mut cmd = Command::new("git");
+            cmd.args(repo.location_args());
+            cmd.args(["log", format, "--date=short", "-E"]);
+            if cli.ignore_case {
+                cmd.arg("-i");
             }
-            Err(e) => {
-                eprintln!("qro: git log in {repo_str}: {e}");
+            if first_only {
+                cmd.arg("--max-count=1");
+            }
+            cmd.args(&cli.git_log_arg);
+            cmd.args(filter);
+
+            let output = match cmd.output() {
+                Ok(output) => output,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    if cli.log_only {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "git is not installed",
+                        ));
+                    }
+                    eprintln!("qro: git not found, skipping log search");
+                    return Ok(matches);
+                }
+                Err(e) => {
+                    eprintln!("qro: git log in {repo_str}: {e}");
+                    continue;
+                }
+            };
+
+            if !output.status.success() {
                 continue;
             }
-        };
 
-        if !output.status.success() {
-            continue;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for (timestamp, m) in parse_log_output(&stdout, &repo_str, *kind)? {
+                // A commit whose message and diff both match is listed once.
+                // Reflog and stash entries are listed on their own even when
+                // they point at a matched commit.
+                if matches!(m.kind, LogMatchKind::Reflog | LogMatchKind::Stash) {
+                    repo_matches.push((timestamp, m));
+                    continue;
+                }
+                match commit_index.get(&m.hash) {
+                    Some(&i) => repo_matches[i].1.kind = LogMatchKind::Both,
+                    None => {
+                        commit_index.insert(m.hash.clone(), repo_matches.len());
+                        repo_matches.push((timestamp, m));
+                    }
+                }
+            }
+            if first_only && !repo_matches.is_empty() {
+                matches.extend(repo_matches.into_iter().map(|(_, m)| m));
+                return Ok(matches);
+            }
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            let mut parts = line.splitn(3, ' ');
-            if let (Some(hash), Some(date), Some(message)) =
-                (parts.next(), parts.next(), parts.next())
-            {
-                matches.push(GitLogMatch {
-                    repo: repo_str.clone(),
-                    hash: hash.to_string(),
-                    date: date.to_string(),
-                    message: message.to_string(),
-                });
-            }
+        if filters.len() > 1 {
+            // Interleave the hits of every filter newest first, like git log.
+            repo_matches.sort_by_key(|(timestamp, _)| std::cmp::Reverse(*timestamp));
         }
+        matches.extend(repo_matches.into_iter().map(|(_, m)| m));
     }
 
     Ok(matches)
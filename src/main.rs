@@ -1,23 +1,38 @@
+mod cache;
 mod cli;
+mod config;
 mod git;
 mod output;
 mod search;
+mod syntax;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io;
+use std::iter;
+use std::path::Path;
 use std::process;
+use std::time::Instant;
 
 use clap::{CommandFactory, Parser};
 
-use cli::Cli;
-use git::{filter_git_log_matches, search_git_log};
-use output::{git_log_blocks, print_blocks};
+use cache::Cache;
+use cli::{Cli, SortBy};
+use git::{filter_git_log_matches, last_commit_times, search_git_log};
+use output::{
+    dir_counts, file_block, file_counts, git_log_blocks, path_block, print_blocks,
+    print_duplicates, print_json, print_stats, write_file_list, Block, LineOptions, Stats,
+};
 use search::{
-    build_exclude_regexes, filter_content_matches, filter_name_matches, prepare_regex_pattern,
-    regex_hint, search_content, search_names, ContentMatch,
+    build_exclude_regexes, dedup_content_matches, drop_stray_context, duplicate_groups,
+    filter_content_matches, filter_name_matches, prepare_regex_pattern, preview_line, regex_hint,
+    search_content, search_names, ContentMatch,
 };
 
-fn run(cli: &Cli) -> io::Result<()> {
+/// The search regex and the `--dont-match` and `--filter-out` regexes.
+type Exclusions = (regex::Regex, Vec<regex::Regex>, Vec<regex::Regex>);
+
+/// Search and print results. Returns whether anything matched.
+fn run(cli: &Cli) -> io::Result<bool> {
     // Validate incompatible flag combinations.
     if cli.log_only && cli.names_only {
         return Err(io::Error::new(
@@ -44,162 +59,397 @@ fn run(cli: &Cli) -> io::Result<()> {
         ));
     }
 
-    // Build exclusion regexes once (empty vecs if no flags given).
+    // Fail upfront on a bad search root rather than reporting a walk error.
+    if let Err(err) = std::fs::metadata(&cli.path) {
+        let reason = if err.kind() == io::ErrorKind::NotFound {
+            "No such file or directory".to_string()
+        } else {
+            err.to_string()
+        };
+        return Err(io::Error::new(
+            err.kind(),
+            format!("{}: {reason}", cli.path),
+        ));
+    }
+
+    // The exclusion filters (-d, -V) and the search pattern they re-run, for
+    // a pass with the given case rule; `None` if no filters were given.
     let has_excludes = !cli.dont_match.is_empty() || !cli.filter_out.is_empty();
-    let (dont_match_res, filter_out_res) = if has_excludes {
-        build_exclude_regexes(cli)?
-    } else {
-        (vec![], vec![])
+    let exclusions = |ignore_case: bool| -> io::Result<Option<Exclusions>> {
+        if !has_excludes {
+            return Ok(None);
+        }
+        let search_re = regex::RegexBuilder::new(&prepare_regex_pattern(cli))
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let (dont_match, filter_out) = build_exclude_regexes(cli, ignore_case)?;
+        Ok(Some((search_re, dont_match, filter_out)))
     };
-    let search_re = if has_excludes {
-        let pattern = prepare_regex_pattern(cli);
-        Some(
-            regex::RegexBuilder::new(&pattern)
-                .case_insensitive(cli.ignore_case)
-                .build()
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
-        )
+
+    // A --hex pattern is a byte sequence, a --line-glob pattern isn't a regex,
+    // --in is about where in the source a match is, the --count modes only
+    // count lines or matches, and --json reports lines as ripgrep does, so
+    // all of them search file contents alone.
+    let contents_only = cli.hex
+        || cli.line_glob
+        || cli.in_context.is_some()
+        || cli.count
+        || cli.count_matches
+        || cli.count_lines_total
+        || cli.count_by_dir.is_some()
+        || cli.json;
+    let search_names_pass = !cli.log_only && !cli.content_only && !contents_only;
+    let search_content_pass = !cli.log_only && !cli.names_only;
+    // --name-only-unmatched and --find-duplicates are about files, so commits
+    // have no place in them.
+    let search_log_pass = (cli.log_only || cli.wants_log())
+        && !contents_only
+        && !cli.name_only_unmatched
+        && !cli.find_duplicates;
+
+    let start = Instant::now();
+    let mut stats = Stats::default();
+
+    let cache = if cli.cached {
+        Cache::open(cli, search_log_pass)?
     } else {
         None
     };
+    if let Some(mut blocks) = cache.as_ref().and_then(Cache::load) {
+        return report(cli, &mut blocks, &mut stats, start);
+    }
 
-    // Log-only mode.
-    if cli.log_only {
-        let mut log_matches = search_git_log(cli)?;
-        if let Some(ref re) = search_re {
-            log_matches =
-                filter_git_log_matches(log_matches, re, &dont_match_res, &filter_out_res);
-        }
-        let mut blocks = git_log_blocks(&log_matches);
-        print_blocks(&mut blocks);
-        return Ok(());
-    }
-
-    // Names-only mode (possibly with --log interleaved).
-    if cli.names_only {
-        let mut name_matches = search_names(cli)?;
-        if let Some(ref re) = search_re {
-            name_matches =
-                filter_name_matches(name_matches, re, &dont_match_res, &filter_out_res);
-        }
-        let mut blocks: Vec<(String, Vec<String>)> = name_matches
-            .into_iter()
-            .map(|m| (m.clone(), vec![m]))
-            .collect();
-        if cli.wants_log() {
-            let mut log_matches = search_git_log(cli)?;
-            if let Some(ref re) = search_re {
-                log_matches = filter_git_log_matches(
-                    log_matches,
-                    re,
-                    &dont_match_res,
-                    &filter_out_res,
-                );
-            }
-            blocks.extend(git_log_blocks(&log_matches));
+    let mut name_vec = Vec::new();
+    if search_names_pass {
+        let phase = Instant::now();
+        name_vec = search_names(cli, cli.names_ignore_case())?;
+        if let Some((re, dont_match, filter_out)) = exclusions(cli.names_ignore_case())? {
+            name_vec = filter_name_matches(name_vec, &re, &dont_match, &filter_out);
         }
-        print_blocks(&mut blocks);
-        return Ok(());
+        stats.name_matches = name_vec.len();
+        stats.phases.push(("name search", phase.elapsed()));
     }
 
-    // Content-only mode (possibly with --log interleaved).
-    if cli.content_only {
-        let mut content_matches = search_content(cli)?;
-        if let Some(ref re) = search_re {
-            content_matches = filter_content_matches(
-                content_matches,
-                re,
-                &dont_match_res,
-                &filter_out_res,
-            );
+    let mut content_matches = BTreeMap::new();
+    if search_content_pass {
+        let phase = Instant::now();
+        content_matches = search_content(cli, cli.contents_ignore_case())?;
+        if let Some((re, dont_match, filter_out)) = exclusions(cli.contents_ignore_case())? {
+            content_matches =
+                filter_content_matches(content_matches, &re, &dont_match, &filter_out);
         }
-        let mut blocks: Vec<(String, Vec<String>)> = content_matches
-            .iter()
-            .map(|(path, matches)| {
-                let mut lines = vec![path.clone()];
-                for m in matches {
-                    match m {
-                        ContentMatch::Line { line_number, line } => {
-                            lines.push(format!("  {line_number}:{line}"));
-                        }
-                        ContentMatch::BinaryFile => {
-                            lines.push("  (binary file matches)".to_string());
-                        }
-                    }
+        if let Some(context) = cli.in_context {
+            let re = syntax::context_regex(&prepare_regex_pattern(cli), cli.contents_ignore_case())?;
+            content_matches = syntax::filter_matches_in(content_matches, context, &re, cli);
+        }
+        if let Some(scope) = cli.dedup {
+            content_matches = dedup_content_matches(content_matches, scope);
+        }
+        if cli.lines_before() > 0 || cli.lines_after() > 0 {
+            content_matches =
+                drop_stray_context(content_matches, cli.lines_before(), cli.lines_after());
+        }
+        if cli.reverse {
+            for matches in content_matches.values_mut() {
+                matches.reverse();
+            }
+        }
+        if cli.trim_trailing {
+            // Display only: every filter above has seen the whole line.
+            for m in content_matches.values_mut().flatten() {
+                if let ContentMatch::Line { line, .. } | ContentMatch::Context { line, .. } = m {
+                    line.truncate(line.trim_end_matches([' ', '\t']).len());
                 }
-                (path.clone(), lines)
-            })
-            .collect();
-        if cli.wants_log() {
-            let mut log_matches = search_git_log(cli)?;
-            if let Some(ref re) = search_re {
-                log_matches = filter_git_log_matches(
-                    log_matches,
-                    re,
-                    &dont_match_res,
-                    &filter_out_res,
-                );
             }
-            blocks.extend(git_log_blocks(&log_matches));
         }
-        print_blocks(&mut blocks);
-        return Ok(());
+        stats.content_lines = content_matches
+            .values()
+            .flatten()
+            .filter(|m| matches!(m, ContentMatch::Line { .. }))
+            .count();
+        stats.phases.push(("content search", phase.elapsed()));
     }
 
-    // Both mode: group by file, optionally with git log interleaved.
-    let mut name_vec = search_names(cli)?;
-    let mut content_matches = search_content(cli)?;
-    if let Some(ref re) = search_re {
-        name_vec = filter_name_matches(name_vec, re, &dont_match_res, &filter_out_res);
-        content_matches = filter_content_matches(
-            content_matches,
-            re,
-            &dont_match_res,
-            &filter_out_res,
-        );
+    // The counting modes and --json print the content matches their own way
+    // instead of as blocks.
+    let counting =
+        cli.count_lines_total || cli.count || cli.count_matches || cli.count_by_dir.is_some();
+    if counting || cli.json {
+        if cli.prints_results() {
+            if cli.count_lines_total {
+                println!("{}", stats.content_lines);
+            } else if let Some(depth) = cli.count_by_dir {
+                for (dir, count) in dir_counts(&content_matches, &cli.path, depth as usize) {
+                    println!("{dir}: {count}");
+                }
+            } else if cli.json {
+                print_json(&content_matches);
+            } else {
+                for (path, count) in file_counts(&content_matches, cli.count_matches) {
+                    println!("{path}:{count}");
+                }
+            }
+        }
+        return report_content_files(cli, &content_matches, &mut stats, start);
     }
-    let name_matches: BTreeSet<String> = name_vec.into_iter().collect();
 
-    let all_paths: BTreeSet<&String> = name_matches.iter().chain(content_matches.keys()).collect();
+    // Name matches with no content match to show get a peek at the file
+    // instead: its first line matching the pattern, or just its first line.
+    let previews: BTreeMap<String, (u64, String)> = if cli.preview {
+        let re = regex::RegexBuilder::new(&prepare_regex_pattern(cli))
+            .case_insensitive(cli.contents_ignore_case())
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        name_vec
+            .iter()
+            .filter(|path| !content_matches.contains_key(*path))
+            .filter_map(|path| Some((path.clone(), preview_line(Path::new(path), &re)?)))
+            .collect()
+    } else {
+        BTreeMap::new()
+    };
 
-    let mut blocks: Vec<(String, Vec<String>)> = all_paths
-        .iter()
-        .map(|path| {
-            let mut lines = vec![path.to_string()];
-            if name_matches.contains(*path) {
-                lines.push("  (name match)".to_string());
-            }
-            if let Some(matches) = content_matches.get(*path) {
-                for m in matches {
-                    match m {
-                        ContentMatch::Line { line_number, line } => {
-                            lines.push(format!("  {line_number}:{line}"));
-                        }
-                        ContentMatch::BinaryFile => {
-                            lines.push("  (binary file matches)".to_string());
-                        }
-                    }
-                }
+    // Group by file. In names-only mode every result is a name match, so the
+    // paths are listed bare instead of annotated.
+    let mut blocks: Vec<Block> = if cli.names_only {
+        name_vec.iter().map(|path| path_block(path)).collect()
+    } else {
+        let match_count_re = if cli.show_match_count {
+            Some(
+                regex::RegexBuilder::new(&prepare_regex_pattern(cli))
+                    .case_insensitive(cli.contents_ignore_case())
+                    .build()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+            )
+        } else {
+            None
+        };
+        let options = LineOptions {
+            match_count_re: match_count_re.as_ref(),
+            limit: cli.max_results_per_file.map(|n| n as usize),
+            line_numbers: !cli.no_line_number,
+            context_breaks: cli.lines_before() > 0 || cli.lines_after() > 0,
+            trailing_context: if cli.reverse { cli.lines_before() } else { cli.lines_after() },
+        };
+        let name_matches: BTreeSet<String> = name_vec.into_iter().collect();
+        let all_paths: BTreeSet<&String> = if cli.name_only_unmatched {
+            name_matches
+                .iter()
+                .filter(|path| !content_matches.contains_key(*path))
+                .collect()
+        } else {
+            name_matches.iter().chain(content_matches.keys()).collect()
+        };
+        all_paths
+            .iter()
+            .map(|path| {
+                file_block(
+                    path,
+                    name_matches.contains(*path),
+                    content_matches.get(*path).map(Vec::as_slice),
+                    &options,
+                )
+            })
+            .collect()
+    };
+
+    for block in &mut blocks {
+        if let Some((line_number, line)) = previews.get(&block.key) {
+            block.lines.push(format!("preview: {line_number}:{line}"));
+        }
+    }
+
+    stats.files_matched = blocks.len();
+
+    if cli.find_duplicates {
+        let groups = duplicate_groups(blocks.iter().map(|b| b.key.as_str()));
+        if cli.prints_results() {
+            print_duplicates(&groups);
+            if groups.is_empty()
+                && let Some(ref marker) = cli.print_empty
+            {
+                println!("{marker}");
             }
-            (path.to_string(), lines)
-        })
-        .collect();
+        }
+        let paths: Vec<&str> =
+            groups.iter().flat_map(|(_, paths)| paths).map(String::as_str).collect();
+        return report_files(cli, &paths, &mut stats, start);
+    }
 
-    if cli.wants_log() {
+    // Git log blocks interleave with the file blocks when sorted.
+    if search_log_pass {
+        let phase = Instant::now();
         let mut log_matches = search_git_log(cli)?;
-        if let Some(ref re) = search_re {
-            log_matches =
-                filter_git_log_matches(log_matches, re, &dont_match_res, &filter_out_res);
+        if let Some((re, dont_match, filter_out)) = exclusions(cli.ignore_case)? {
+            log_matches = filter_git_log_matches(log_matches, &re, &dont_match, &filter_out);
         }
+        stats.commits = log_matches.len();
+        stats.phases.push(("git log", phase.elapsed()));
         blocks.extend(git_log_blocks(&log_matches));
     }
-    print_blocks(&mut blocks);
+    if let Some(ref cache) = cache
+        && let Err(err) = cache.store(&blocks)
+    {
+        eprintln!("qro: couldn't update the cache: {err}");
+    }
 
+    report(cli, &mut blocks, &mut stats, start)
+}
+
+/// Print the result blocks, the `--print-empty` marker and `--stats`, and
+/// write the `--print-files-to` list. Returns whether anything matched.
+fn report(cli: &Cli, blocks: &mut [Block], stats: &mut Stats, start: Instant) -> io::Result<bool> {
+    if cli.prints_results() {
+        // Only the files that matched are looked up.
+        let commit_times = if cli.sort == SortBy::GitModified {
+            let files = blocks.iter().filter(|b| !b.is_log).map(|b| b.key.as_str());
+            last_commit_times(&cli.path, files)
+        } else {
+            HashMap::new()
+        };
+        print_blocks(blocks, cli, &commit_times);
+        if blocks.is_empty()
+            && let Some(ref marker) = cli.print_empty
+        {
+            println!("{marker}");
+        }
+        if cli.stats {
+            stats.total = start.elapsed();
+            print_stats(stats);
+        }
+    }
+    if let Some(ref dest) = cli.print_files_to {
+        // Git log blocks aren't files.
+        let paths = blocks.iter().filter(|b| !b.is_log).map(|b| b.key.as_str());
+        write_file_list(paths, dest)?;
+    }
+
+    Ok(!blocks.is_empty())
+}
+
+/// [`report_files`] for the modes that print content matches without
+/// building blocks. Only files with matching lines count, as in the counts
+/// those modes print.
+fn report_content_files(
+    cli: &Cli,
+    content_matches: &BTreeMap<String, Vec<ContentMatch>>,
+    stats: &mut Stats,
+    start: Instant,
+) -> io::Result<bool> {
+    let paths: Vec<&str> = content_matches
+        .iter()
+        .filter(|(_, matches)| matches.iter().any(|m| matches!(m, ContentMatch::Line { .. })))
+        .map(|(path, _)| path.as_str())
+        .collect();
+    report_files(cli, &paths, stats, start)
+}
+
+/// The `--stats` and `--print-files-to` parts of [`report`], for the modes
+/// that print their results as something other than blocks.
+fn report_files(cli: &Cli, paths: &[&str], stats: &mut Stats, start: Instant) -> io::Result<bool> {
+    stats.files_matched = paths.len();
+    if cli.prints_results() && cli.stats {
+        stats.total = start.elapsed();
+        print_stats(stats);
+    }
+    if let Some(ref dest) = cli.print_files_to {
+        write_file_list(paths.iter().copied(), dest)?;
+    }
+
+    Ok(!paths.is_empty())
+}
+
+/// Run the search `iterations` times without printing results and report
+/// the spread of wall-clock times on stderr (`--benchmark`).
+fn benchmark(cli: &Cli, iterations: u32) -> io::Result<()> {
+    let mut times = Vec::new();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        run(cli)?;
+        times.push(start.elapsed());
+    }
+    times.sort();
+    eprintln!(
+        "qro: {iterations} runs: min {:.3}s, median {:.3}s, max {:.3}s",
+        times[0].as_secs_f64(),
+        times[times.len() / 2].as_secs_f64(),
+        times[times.len() - 1].as_secs_f64(),
+    );
     Ok(())
 }
 
+/// Version details for bug reports, in the spirit of `rg --version`.
+fn version_verbose() -> String {
+    let git = match process::Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => "not found".to_string(),
+    };
+    let build = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!(
+        "qro {version}\n\
+         build: {build}, {os}/{arch}\n\
+         regex engine: Rust regex (via grep-regex)\n\
+         PCRE2: not available\n\
+         cargo features: none\n\
+         git: {git}\n",
+        version = env!("CARGO_PKG_VERSION"),
+        os = std::env::consts::OS,
+        arch = std::env::consts::ARCH,
+    )
+}
+
+/// Parse the command line, with defaults from any `.qroconfig` files for the
+/// search path inserted ahead of the real arguments so the CLI wins.
+fn parse_args() -> Cli {
+    let mut cli = Cli::parse();
+    if cli.expand_env {
+        cli.expand_env_vars();
+    }
+    if cli.no_config {
+        return cli;
+    }
+
+    // With --patterns-from, the first positional argument is the search path.
+    let search_path = match cli.pattern {
+        Some(ref path) if cli.patterns_from.is_some() => path,
+        _ => &cli.path,
+    };
+    match config::config_args(search_path) {
+        Ok(config_args) if !config_args.is_empty() => {
+            let mut args = std::env::args_os();
+            let bin = args.next().unwrap_or_default();
+            cli = Cli::parse_from(iter::once(bin).chain(config_args).chain(args));
+            if cli.expand_env {
+                cli.expand_env_vars();
+            }
+            cli
+        }
+        Ok(_) => cli,
+        Err(err) => {
+            eprintln!("qro: {err}");
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
-    let cli = Cli::parse();
+    let mut cli = parse_args();
+    if let Err(err) = cli.load_patterns() {
+        eprintln!("qro: {err}");
+        process::exit(1);
+    }
+
+    if cli.version_verbose {
+        print!("{}", version_verbose());
+        return;
+    }
 
     if let Some(shell) = cli.completions {
         clap_complete::generate(shell, &mut Cli::command(), "qro", &mut io::stdout());
@@ -210,13 +460,28 @@ fn main() {
         eprintln!("{cli:?}");
     }
 
-    if let Err(err) = run(&cli) {
-        eprintln!("qro: {err}");
-        if let Some(pattern) = &cli.pattern {
-            if let Some(hint) = regex_hint(pattern) {
+    if let Some(iterations) = cli.benchmark {
+        if let Err(err) = benchmark(&cli, iterations) {
+            eprintln!("qro: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    match run(&cli) {
+        Ok(found) => {
+            if cli.quiet && !found {
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("qro: {err}");
+            if let Some(pattern) = &cli.pattern
+                && let Some(hint) = regex_hint(pattern)
+            {
                 eprintln!("\n{hint}");
             }
+            process::exit(1);
         }
-        process::exit(1);
     }
 }
@@ -1,40 +1,547 @@
-use std::collections::BTreeMap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::git::GitLogMatch;
+use regex::Regex;
+
+use crate::cli::{Cli, SortBy, SortRepos};
+use crate::git::{GitLogMatch, LogMatchKind};
+use crate::search::ContentMatch;
+
+/// A group of output lines that sort and print together.
+pub(crate) struct Block {
+    /// Sort key; usually the file path.
+    pub key: String,
+    /// Whether this is a git log block. On equal keys, file blocks sort first.
+    pub is_log: bool,
+    /// Heading line: a file path or a repo's git log label.
+    pub heading: String,
+    /// Separator between heading and line when headings are printed inline.
+    pub inline_sep: &'static str,
+    /// Detail lines, unindented.
+    pub lines: Vec<String>,
+    /// Indices into `lines` of context lines (`-A`/`-B`/`-C`), which
+    /// `--no-heading` joins to the heading with `-` instead of `inline_sep`.
+    pub context_lines: BTreeSet<usize>,
+    /// One-line overview of the block, printed instead of it in `--summary` mode.
+    pub summary: String,
+}
+
+/// How [`file_block`] renders content matches.
+#[derive(Default)]
+pub(crate) struct LineOptions<'a> {
+    /// Follow each line with the number of times this regex matches it
+    /// (`--show-match-count`).
+    pub match_count_re: Option<&'a Regex>,
+    /// Replace lines past this many with a count (`--max-results-per-file`).
+    /// The summary still counts them all.
+    pub limit: Option<usize>,
+    /// Prefix each line with its number (unless `--no-line-number`).
+    pub line_numbers: bool,
+    /// Put a [`CONTEXT_BREAK`] between lines that aren't adjacent in the
+    /// file (with `-A`/`-B`/`-C`).
+    pub context_breaks: bool,
+    /// How many context lines can follow a match in the listing: `-A`, or
+    /// `-B` with `--reverse`. Past `limit`, only these are shown.
+    pub trailing_context: u64,
+}
+
+/// Separates groups of matches and their context lines within a file.
+pub(crate) const CONTEXT_BREAK: &str = "--";
+
+/// Build a block for a file path with its name-match annotation and content matches.
+pub(crate) fn file_block(
+    path: &str,
+    name_match: bool,
+    content: Option<&[ContentMatch]>,
+    options: &LineOptions,
+) -> Block {
+    let mut lines = Vec::new();
+    let mut context_lines = BTreeSet::new();
+    if name_match {
+        lines.push("(name match)".to_string());
+    }
+    let mut content_lines = 0;
+    let mut hidden = 0;
+    let mut binary = false;
+    let mut prev_line_number: Option<u64> = None;
+    let mut last_match_line: Option<u64> = None;
+    for m in content.unwrap_or_default() {
+        let is_content_line = !matches!(m, ContentMatch::BinaryFile | ContentMatch::Context { .. });
+        if is_content_line && options.limit.is_some_and(|limit| content_lines >= limit) {
+            content_lines += 1;
+            hidden += 1;
+            continue;
+        }
+        if let ContentMatch::Line { line_number, .. } | ContentMatch::Context { line_number, .. } = m
+        {
+            // Once matches are being hidden, so is their context, and the
+            // last shown match keeps only its own trailing context.
+            let limit_reached = options.limit.is_some_and(|limit| content_lines >= limit);
+            let trails_last_match = last_match_line
+                .is_some_and(|last| last.abs_diff(*line_number) <= options.trailing_context);
+            if hidden > 0
+                || (limit_reached
+                    && matches!(m, ContentMatch::Context { .. })
+                    && !trails_last_match)
+            {
+                continue;
+            }
+            // abs_diff, since --reverse lists lines last to first.
+            if options.context_breaks
+                && prev_line_number.is_some_and(|prev| prev.abs_diff(*line_number) > 1)
+            {
+                lines.push(CONTEXT_BREAK.to_string());
+            }
+            prev_line_number = Some(*line_number);
+            if let ContentMatch::Line { .. } = m {
+                last_match_line = Some(*line_number);
+            }
+        }
+        match m {
+            ContentMatch::Line { line_number, line, .. } => {
+                let mut entry = if options.line_numbers {
+                    format!("{line_number}:{line}")
+                } else {
+                    line.clone()
+                };
+                if let Some(re) = options.match_count_re {
+                    let n = re.find_iter(line).count();
+                    entry.push_str(&format!(" ({n} match{})", if n == 1 { "" } else { "es" }));
+                }
+                lines.push(entry);
+                content_lines += 1;
+            }
+            ContentMatch::Context { line_number, line, .. } => {
+                context_lines.insert(lines.len());
+                lines.push(if options.line_numbers {
+                    format!("{line_number}-{line}")
+                } else {
+                    line.clone()
+                });
+            }
+            ContentMatch::BinaryFile => {
+                lines.push("(binary file matches)".to_string());
+                binary = true;
+            }
+            ContentMatch::Offset(offset) => {
+                lines.push(format!("byte {offset} (0x{offset:x})"));
+                content_lines += 1;
+            }
+            ContentMatch::HexDump { offset, rows } => {
+                lines.push(format!("byte {offset} (0x{offset:x}):"));
+                lines.extend(rows.iter().map(|row| format!("  {row}")));
+                content_lines += 1;
+            }
+        }
+    }
+    if hidden > 0 {
+        lines.push(format!("... ({hidden} more)"));
+    }
+
+    let mut parts = Vec::new();
+    if content_lines > 0 {
+        parts.push(format!("{content_lines} content"));
+    }
+    if binary {
+        parts.push("binary".to_string());
+    }
+    if name_match {
+        parts.push("name match".to_string());
+    }
+    let summary = if parts.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path} ({})", parts.join(", "))
+    };
+
+    Block {
+        key: path.to_string(),
+        is_log: false,
+        heading: path.to_string(),
+        inline_sep: ":",
+        lines,
+        context_lines,
+        summary,
+    }
+}
+
+/// Build a block for a bare path with no detail lines (names-only mode).
+pub(crate) fn path_block(path: &str) -> Block {
+    file_block(path, false, None, &LineOptions::default())
+}
 
 /// Convert git log matches into output blocks keyed by repo path for interleaved sorting.
-pub(crate) fn git_log_blocks(log_matches: &[GitLogMatch]) -> Vec<(String, Vec<String>)> {
+pub(crate) fn git_log_blocks(log_matches: &[GitLogMatch]) -> Vec<Block> {
     let mut by_repo: BTreeMap<&str, Vec<&GitLogMatch>> = BTreeMap::new();
     for m in log_matches {
         by_repo.entry(&m.repo).or_default().push(m);
     }
     by_repo
         .into_iter()
-        .map(|(repo, matches)| {
-            let mut lines = vec![format!("{repo} (git log):")];
-            for m in matches {
-                lines.push(format!("  {} {} {}", m.hash, m.date, m.message));
-            }
-            // Sort after all files within the repo directory.
-            (format!("{repo}/\x7f"), lines)
+        .map(|(repo, matches)| Block {
+            // Sort after all files within the repo directory. `char::MAX`
+            // is the largest UTF-8 sequence, so even non-ASCII names sort
+            // first; a file whose name is exactly that character ties, and
+            // `is_log` breaks the tie.
+            key: format!("{repo}/{}", char::MAX),
+            is_log: true,
+            heading: format!("{repo} (git log):"),
+            inline_sep: " ",
+            lines: matches
+                .iter()
+                .map(|m| {
+                    let annotation = match m.kind {
+                        LogMatchKind::Message => "",
+                        LogMatchKind::Diff => " (diff match)",
+                        LogMatchKind::Both => " (message and diff match)",
+                        LogMatchKind::Reflog => " (reflog)",
+                        LogMatchKind::Stash => " (stash)",
+                    };
+                    format!("{} {} {}{annotation}", m.hash, m.date, m.message)
+                })
+                .collect(),
+            context_lines: BTreeSet::new(),
+            summary: format!(
+                "{repo} (git log, {} commit{})",
+                matches.len(),
+                if matches.len() == 1 { "" } else { "s" }
+            ),
+        })
+        .collect()
+}
+
+/// Matching lines per directory for `--count-by-dir`: each file counts
+/// toward its ancestor `depth` levels below `root`, or toward its own
+/// directory if that's shallower. Directories are shown with a trailing `/`.
+pub(crate) fn dir_counts(
+    content_matches: &BTreeMap<String, Vec<ContentMatch>>,
+    root: &str,
+    depth: usize,
+) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for (path, matches) in content_matches {
+        let lines = matches
+            .iter()
+            .filter(|m| matches!(m, ContentMatch::Line { .. }))
+            .count();
+        if lines == 0 {
+            continue;
+        }
+        let path = Path::new(path);
+        let dir = match path.strip_prefix(root).ok().and_then(Path::parent) {
+            Some(rel_dir) => Path::new(root).join(rel_dir.iter().take(depth).collect::<PathBuf>()),
+            // The search path is the file itself.
+            None => path.parent().unwrap_or(path).to_path_buf(),
+        };
+        let mut dir = dir.display().to_string();
+        if !dir.ends_with('/') {
+            dir.push('/');
+        }
+        *counts.entry(dir).or_default() += lines;
+    }
+    counts
+}
+
+/// Matching lines per file for `--count`, or with `matches`, individual
+/// matches (`--count-matches`). Files with none, such as binary files, are
+/// left out.
+pub(crate) fn file_counts(
+    content_matches: &BTreeMap<String, Vec<ContentMatch>>,
+    matches: bool,
+) -> Vec<(&str, usize)> {
+    content_matches
+        .iter()
+        .filter_map(|(path, file_matches)| {
+            let count: usize = file_matches
+                .iter()
+                .map(|m| match m {
+                    ContentMatch::Line { submatches, .. } if matches => submatches.len(),
+                    ContentMatch::Line { .. } => 1,
+                    _ => 0,
+                })
+                .sum();
+            (count > 0).then_some((path.as_str(), count))
         })
         .collect()
 }
 
-/// Sort output blocks by key and print with blank lines between multi-line blocks.
-pub(crate) fn print_blocks(blocks: &mut Vec<(String, Vec<String>)>) {
-    blocks.sort_by(|a, b| a.0.cmp(&b.0));
+/// Number of path separators in a block key.
+fn depth(key: &str) -> usize {
+    key.chars().filter(|&c| std::path::is_separator(c)).count()
+}
+
+/// Last path component of a block key. Git log keys end in a `char::MAX`
+/// component, so they sort after every file name.
+fn basename(key: &str) -> &str {
+    key.rsplit(std::path::is_separator).next().unwrap_or(key)
+}
+
+/// Directory name of the repo behind a git log block key, which is
+/// `{repo}/{char::MAX}`.
+fn repo_name(key: &str) -> &str {
+    key.rsplit(std::path::is_separator).nth(1).unwrap_or("")
+}
+
+/// Compare blocks by key, then file blocks before git log blocks.
+fn cmp_key(a: &Block, b: &Block) -> Ordering {
+    a.key.cmp(&b.key).then(a.is_log.cmp(&b.is_log))
+}
+
+/// Sort blocks according to `--sort`, given the
+/// [`last_commit_times`](crate::git::last_commit_times) of the files for
+/// `git-modified`. Ties always fall back to [`cmp_key`].
+fn sort_blocks(blocks: &mut [Block], cli: &Cli, commit_times: &HashMap<String, i64>) {
+    match cli.sort {
+        SortBy::Path => blocks.sort_by(cmp_key),
+        SortBy::Depth => blocks.sort_by(|a, b| {
+            depth(&a.key)
+                .cmp(&depth(&b.key))
+                .then_with(|| cmp_key(a, b))
+        }),
+        SortBy::Name => blocks.sort_by(|a, b| {
+            basename(&a.key)
+                .cmp(basename(&b.key))
+                .then_with(|| cmp_key(a, b))
+        }),
+        SortBy::GitModified => {
+            // Blocks with no commit time (untracked files, git log blocks)
+            // go last. The key includes everything `cmp_key` compares.
+            blocks.sort_by_cached_key(|b| {
+                let time = commit_times.get(&b.key).filter(|_| !b.is_log);
+                (Reverse(time.copied()), b.key.clone(), b.is_log)
+            });
+        }
+    }
+}
+
+/// Apply `--sort-repos`: unless it's `path`, move the git log blocks after
+/// the file blocks and order them by repo name or by commit count.
+fn sort_repo_blocks(blocks: &mut [Block], sort: SortRepos) {
+    if sort == SortRepos::Path {
+        return;
+    }
+    // Stable, so the file blocks keep their order.
+    blocks.sort_by_key(|b| b.is_log);
+    let first_log = blocks.iter().position(|b| b.is_log).unwrap_or(blocks.len());
+    let logs = &mut blocks[first_log..];
+    match sort {
+        SortRepos::Path => {}
+        SortRepos::Name => logs.sort_by(|a, b| {
+            repo_name(&a.key)
+                .cmp(repo_name(&b.key))
+                .then_with(|| cmp_key(a, b))
+        }),
+        SortRepos::Commits => logs.sort_by(|a, b| {
+            b.lines
+                .len()
+                .cmp(&a.lines.len())
+                .then_with(|| cmp_key(a, b))
+        }),
+    }
+}
+
+/// Sort output blocks and print them.
+///
+/// With headings (the default), each block prints its heading followed by its
+/// indented detail lines, and a blank line separates two adjacent blocks
+/// unless both are bare paths. That keeps `-n` output a plain list while
+/// giving every block with details a visual boundary. `--indent` sets how far
+/// the detail lines are indented. `--group-separator`
+/// replaces the blank line, and `--compact` drops it. `--no-heading` prints
+/// one `heading:line` per detail line with no blank lines, which is the
+/// easiest form to parse from scripts.
+/// `--summary` prints only each block's one-line summary.
+pub(crate) fn print_blocks(blocks: &mut [Block], cli: &Cli, commit_times: &HashMap<String, i64>) {
+    sort_blocks(blocks, cli, commit_times);
+    sort_repo_blocks(blocks, cli.sort_repos);
+
+    if cli.summary {
+        for block in blocks.iter() {
+            println!("{}", block.summary);
+        }
+        return;
+    }
+
+    if cli.no_heading {
+        for block in blocks.iter() {
+            if block.lines.is_empty() {
+                println!("{}", block.heading);
+            }
+            for (i, line) in block.lines.iter().enumerate() {
+                if !block.is_log && line == CONTEXT_BREAK {
+                    println!("{CONTEXT_BREAK}");
+                } else if block.context_lines.contains(&i) {
+                    // As in ripgrep, so scripts splitting on `:` skip them.
+                    println!("{}-{line}", block.heading);
+                } else {
+                    println!("{}{}{line}", block.heading, block.inline_sep);
+                }
+            }
+        }
+        return;
+    }
+
+    let separator = if cli.compact || cli.no_group_separator {
+        None
+    } else {
+        Some(cli.group_separator.as_deref().unwrap_or(""))
+    };
     let mut prev_multi = false;
     let mut first = true;
-    for (_, lines) in blocks.iter() {
-        let multi = lines.len() > 1;
-        if !first && (multi || prev_multi) {
-            println!();
+    for block in blocks.iter() {
+        let multi = !block.lines.is_empty();
+        if let Some(separator) = separator
+            && !first
+            && (multi || prev_multi)
+        {
+            println!("{separator}");
         }
         first = false;
-        for line in lines {
-            println!("{line}");
+        println!("{}", block.heading);
+        for line in &block.lines {
+            println!("{:indent$}{line}", "", indent = cli.indent);
         }
         prev_multi = multi;
     }
 }
+
+/// Print `--find-duplicates` groups, each with a size line over its paths
+/// and a blank line between groups.
+pub(crate) fn print_duplicates(groups: &[(u64, Vec<String>)]) {
+    for (i, (size, paths)) in groups.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{} identical files, {size} bytes each:", paths.len());
+        for path in paths {
+            println!("  {path}");
+        }
+    }
+}
+
+/// Version of the `--json` output, given in its leading `qro` record. It only
+/// goes up for changes that break existing readers, such as a removed or
+/// retyped field; new fields and record types don't change it.
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Print content matches as ripgrep's `--json` event stream, after a `qro`
+/// record with the [`JSON_SCHEMA_VERSION`]: for each file a `begin` record, a
+/// `match` or `context` record per line, and an `end` record. Unlike
+/// ripgrep's, `end` only counts matched lines and matches, and line text is
+/// always given as `text` (lossily decoded), never `bytes`. Lines keep any
+/// carriage returns, so submatch offsets index the line as stored.
+pub(crate) fn print_json(content_matches: &BTreeMap<String, Vec<ContentMatch>>) {
+    // ripgrep's readers skip record types they don't know.
+    println!("{{\"type\":\"qro\",\"data\":{{\"schema_version\":{JSON_SCHEMA_VERSION}}}}}");
+    for (path, matches) in content_matches {
+        let path = format!("{{\"text\":{}}}", json_string(path));
+        println!("{{\"type\":\"begin\",\"data\":{{\"path\":{path}}}}}");
+        let mut matched_lines = 0;
+        let mut match_count = 0;
+        for m in matches {
+            let (kind, line_number, line, offset, newline, submatches) = match m {
+                ContentMatch::Line { line_number, line, offset, newline, submatches } => {
+                    // --trim-trailing may have cut a span short.
+                    let spans: Vec<String> = submatches
+                        .iter()
+                        .filter_map(|span| {
+                            let text = line.get(span.clone())?;
+                            Some(format!(
+                                "{{\"match\":{{\"text\":{}}},\"start\":{},\"end\":{}}}",
+                                json_string(text),
+                                span.start,
+                                span.end
+                            ))
+                        })
+                        .collect();
+                    matched_lines += 1;
+                    match_count += spans.len();
+                    ("match", line_number, line, offset, newline, spans)
+                }
+                ContentMatch::Context { line_number, line, offset, newline } => {
+                    ("context", line_number, line, offset, newline, Vec::new())
+                }
+                // --json rules out the modes that produce these, and binary
+                // files have no lines to report.
+                ContentMatch::BinaryFile | ContentMatch::Offset(_) | ContentMatch::HexDump { .. } => {
+                    continue;
+                }
+            };
+            println!(
+                "{{\"type\":\"{kind}\",\"data\":{{\"path\":{path},\"lines\":{{\"text\":{}}},\
+                 \"line_number\":{line_number},\"absolute_offset\":{offset},\"submatches\":[{}]}}}}",
+                json_string(&format!("{line}{}", if *newline { "\n" } else { "" })),
+                submatches.join(",")
+            );
+        }
+        println!(
+            "{{\"type\":\"end\",\"data\":{{\"path\":{path},\"binary_offset\":null,\
+             \"stats\":{{\"matched_lines\":{matched_lines},\"matches\":{match_count}}}}}}}"
+        );
+    }
+}
+
+/// Quote and escape `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Write the paths of matched files, each followed by a NUL byte, to `dest`
+/// (`--print-files-to`).
+pub(crate) fn write_file_list<'a>(
+    paths: impl IntoIterator<Item = &'a str>,
+    dest: &Path,
+) -> io::Result<()> {
+    let file = File::create(dest)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", dest.display())))?;
+    let mut out = BufWriter::new(file);
+    for path in paths {
+        out.write_all(path.as_bytes())?;
+        out.write_all(b"\0")?;
+    }
+    out.flush()
+}
+
+/// Counts and per-phase timings for `--stats`.
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub files_matched: usize,
+    pub name_matches: usize,
+    pub content_lines: usize,
+    pub commits: usize,
+    /// Time spent in each phase that ran, in the order they ran.
+    pub phases: Vec<(&'static str, Duration)>,
+    pub total: Duration,
+}
+
+/// Print the `--stats` block after the results.
+pub(crate) fn print_stats(stats: &Stats) {
+    println!();
+    println!("stats:");
+    println!("  files matched: {}", stats.files_matched);
+    println!("  name matches: {}", stats.name_matches);
+    println!("  content lines: {}", stats.content_lines);
+    println!("  commits: {}", stats.commits);
+    for (phase, elapsed) in &stats.phases {
+        println!("  {phase}: {:.3}s", elapsed.as_secs_f64());
+    }
+    println!("  total: {:.3}s", stats.total.as_secs_f64());
+}
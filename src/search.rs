@@ -1,21 +1,78 @@
-use std::collections::BTreeMap;
-use std::io;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use grep_regex::RegexMatcherBuilder;
-use grep_searcher::{BinaryDetection, Searcher, SearcherBuilder, Sink, SinkFinish, SinkMatch};
-use ignore::WalkBuilder;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use globset::GlobBuilder;
+use grep_matcher::{LineTerminator, Matcher};
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{
+    BinaryDetection, Encoding, Searcher, SearcherBuilder, Sink, SinkContext, SinkFinish,
+    SinkMatch,
+};
+use ignore::{WalkBuilder, WalkState};
 
-use crate::cli::Cli;
+use crate::cli::{Cli, Dedup};
+use crate::git::discover_git_repos;
 
+/// A content match within one file.
+///
+/// `search_content` guarantees that a file's matches are in ascending line
+/// order, so the output stage can emit them as-is.
 pub(crate) enum ContentMatch {
-    Line { line_number: u64, line: String },
+    Line {
+        line_number: u64,
+        line: String,
+        /// Byte offset of the line's start in the file.
+        offset: u64,
+        /// Whether the line ended in a newline in the file; the last line
+        /// may not.
+        newline: bool,
+        /// Byte ranges of the pattern's matches within `line`. Only recorded
+        /// for `--json` and `--count-matches`.
+        submatches: Vec<Range<usize>>,
+    },
+    /// A line shown around a match for `-A`/`-B`/`-C`, not itself a match.
+    Context { line_number: u64, line: String, offset: u64, newline: bool },
     BinaryFile,
+    /// A `--hex` match, by byte offset from the start of the file.
+    Offset(u64),
+    /// A `--binary-hex` match in a binary file: its byte offset and an
+    /// `xxd`-style dump of the bytes around it.
+    HexDump { offset: u64, rows: Vec<String> },
+}
+
+impl ContentMatch {
+    /// Sort key for ordering matches within a file. Binary placeholders
+    /// carry no line, so they sort after any real lines.
+    fn sort_key(&self) -> u64 {
+        match self {
+            ContentMatch::Line { line_number, .. }
+            | ContentMatch::Context { line_number, .. } => *line_number,
+            ContentMatch::BinaryFile => u64::MAX,
+            ContentMatch::Offset(offset) | ContentMatch::HexDump { offset, .. } => *offset,
+        }
+    }
 }
 
 /// Sink that collects content matches and detects binary files.
 struct ContentSink {
     matches: Vec<ContentMatch>,
     saw_binary: bool,
+    /// Inclusive 1-based line window; matches outside it are dropped.
+    line_range: Option<(u64, u64)>,
+    /// Remove carriage returns from reported lines (unless `--keep-cr`).
+    strip_cr: bool,
+    /// With `--line-glob`, the searcher reports every line and only those
+    /// matching this glob are kept.
+    line_glob: Option<globset::GlobMatcher>,
+    /// With `--json` or `--count-matches`, the matcher to find each match
+    /// within a line.
+    submatcher: Option<RegexMatcher>,
 }
 
 impl Sink for ContentSink {
@@ -23,11 +80,56 @@ impl Sink for ContentSink {
 
     fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
         let line_number = mat.line_number().unwrap_or(0);
-        let line = String::from_utf8_lossy(mat.bytes())
-            .trim_end_matches('\n')
-            .trim_end_matches('\r')
-            .to_string();
-        self.matches.push(ContentMatch::Line { line_number, line });
+        if let Some((start, end)) = self.line_range {
+            if line_number > end {
+                // Nothing further in this file can be in range.
+                return Ok(false);
+            }
+            if line_number < start {
+                return Ok(true);
+            }
+        }
+        let line = line_text(mat.bytes(), self.strip_cr);
+        if let Some(ref glob) = self.line_glob
+            && !glob.is_match(&line)
+        {
+            return Ok(true);
+        }
+        // Spans are found in the line as reported, so they index `line` even
+        // when carriage returns were stripped from it.
+        let mut submatches = Vec::new();
+        if let Some(ref matcher) = self.submatcher {
+            let _ = matcher.find_iter(line.as_bytes(), |m| {
+                submatches.push(m.start()..m.end());
+                true
+            });
+        }
+        self.matches.push(ContentMatch::Line {
+            line_number,
+            line,
+            offset: mat.absolute_byte_offset(),
+            newline: mat.bytes().ends_with(b"\n"),
+            submatches,
+        });
+        Ok(true)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, io::Error> {
+        let line_number = context.line_number().unwrap_or(0);
+        if let Some((start, end)) = self.line_range
+            && !(start..=end).contains(&line_number)
+        {
+            return Ok(true);
+        }
+        // The searcher never reports a line twice, so overlapping windows
+        // come out merged.
+        let line = line_text(context.bytes(), self.strip_cr);
+        self.matches.push(ContentMatch::Context {
+            line_number,
+            line,
+            offset: context.absolute_byte_offset(),
+            newline: context.bytes().ends_with(b"\n"),
+        });
         Ok(true)
     }
 
@@ -39,13 +141,65 @@ impl Sink for ContentSink {
     }
 }
 
-pub(crate) fn build_walker(cli: &Cli) -> io::Result<ignore::Walk> {
+/// The text of a matched line for display, without its line terminator.
+///
+/// With `strip_cr`, every carriage return goes, not just the one in a CRLF
+/// ending: a bare CR mid-line would otherwise make the terminal overwrite the
+/// start of the line. Without it, the line is shown as stored, including the
+/// CR of a CRLF ending.
+fn line_text(bytes: &[u8], strip_cr: bool) -> String {
+    let line = String::from_utf8_lossy(bytes);
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    if strip_cr {
+        line.replace('\r', "")
+    } else {
+        line.to_string()
+    }
+}
+
+/// A predicate deciding whether the walker keeps (and descends into) an entry.
+type EntryFilter = Box<dyn Fn(&ignore::DirEntry) -> bool + Send + Sync>;
+
+/// Location of qro's own global ignore file: `$XDG_CONFIG_HOME/qro/ignore`,
+/// falling back to `~/.config/qro/ignore`.
+fn global_ignore_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("qro").join("ignore"))
+}
+
+pub(crate) fn build_walker(cli: &Cli) -> io::Result<WalkBuilder> {
     let mut walker = WalkBuilder::new(&cli.path);
     walker
-        .hidden(!cli.hidden)
-        .git_ignore(!cli.no_ignore)
-        .ignore(!cli.no_ignore);
+        .hidden(!cli.hidden && !cli.hidden_only)
+        .git_ignore(!cli.no_ignore && !cli.no_ignore_vcs)
+        .git_exclude(!cli.no_ignore && !cli.no_ignore_vcs)
+        .git_global(!cli.no_ignore && !cli.no_ignore_vcs)
+        .ignore(!cli.no_ignore && !cli.no_ignore_dot);
+
+    if !cli.no_ignore
+        && !cli.no_ignore_global_qro
+        && let Some(path) = global_ignore_path().filter(|p| p.is_file())
+        && let Some(err) = walker.add_ignore(&path)
+    {
+        eprintln!("qro: {}: {err}", path.display());
+    }
+    // Added after the global file, so they take precedence over it. Unlike
+    // the global file, a bad --exclude-file is an error: the user asked for it.
+    for path in &cli.exclude_file {
+        if let Some(err) = walker.add_ignore(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--exclude-file {}: {err}", path.display()),
+            ));
+        }
+    }
 
+    // Overrides are last-match-wins, so adding every -x exclusion after the
+    // -g glob means an exclusion always beats the include when both match,
+    // regardless of the order the flags were given on the command line.
     if cli.glob.is_some() || !cli.exclude.is_empty() {
         let mut overrides = ignore::overrides::OverrideBuilder::new(&cli.path);
         if let Some(ref glob) = cli.glob {
@@ -65,59 +219,438 @@ pub(crate) fn build_walker(cli: &Cli) -> io::Result<ignore::Walk> {
         );
     }
 
+    // WalkBuilder keeps only one entry filter, so collect them and install
+    // a single closure that requires all of them to pass.
+    let mut entry_filters: Vec<EntryFilter> = Vec::new();
+
+    if let Some(pruned) = pruned_dirs(cli)? {
+        entry_filters.push(Box::new(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            !(is_dir && entry.depth() > 0 && pruned.matched(entry.path(), true).is_ignore())
+        }));
+    }
+
+    if !cli.stop_at.is_empty() {
+        let stop_at = cli.stop_at.clone();
+        entry_filters.push(Box::new(move |entry| {
+            // Never prune the search root itself, even if it has a stop name.
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            !(is_dir
+                && entry.depth() > 0
+                && stop_at
+                    .iter()
+                    .any(|name| entry.file_name() == name.as_str()))
+        }));
+    }
+
+    if cli.repos_only {
+        // The repos are the search path's own (which leaves nothing to
+        // filter) and its immediate children, walked with the same prefix.
+        let root = Path::new(&cli.path).canonicalize()?;
+        let repos = discover_git_repos(&cli.path);
+        let inside_repo = repos
+            .iter()
+            .any(|repo| repo.canonicalize().is_ok_and(|repo| root.starts_with(repo)));
+        if !inside_repo {
+            entry_filters.push(Box::new(move |entry| {
+                entry.depth() == 0 || repos.iter().any(|repo| entry.path().starts_with(repo))
+            }));
+        }
+    }
+
+    if cli.hidden_only {
+        // Descend everywhere, since dotfiles can live in ordinary directories,
+        // but keep only files with a hidden component below the search root.
+        entry_filters.push(Box::new(|entry| {
+            entry.file_type().is_none_or(|t| t.is_dir())
+                || entry
+                    .path()
+                    .components()
+                    .rev()
+                    .take(entry.depth())
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        }));
+    }
+
+    if cli.only_empty || cli.skip_empty {
+        // Files whose size can't be read are kept, so reading them reports
+        // the error.
+        let want_empty = cli.only_empty;
+        entry_filters.push(Box::new(move |entry| {
+            entry.file_type().is_none_or(|t| t.is_dir())
+                || entry.metadata().map_or(true, |meta| (meta.len() == 0) == want_empty)
+        }));
+    }
+
+    if let Some(ref name) = cli.name {
+        // Like -g, but a regex on the file name alone.
+        let re = regex::RegexBuilder::new(name)
+            .case_insensitive(cli.names_ignore_case())
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        entry_filters.push(Box::new(move |entry| {
+            entry.file_type().is_none_or(|t| t.is_dir())
+                || re.is_match(&entry.file_name().to_string_lossy())
+        }));
+    }
+
     if let Some(ref ft) = cli.file_type {
         let mut types_builder = ignore::types::TypesBuilder::new();
         types_builder.add_defaults();
+        check_file_type(&types_builder, ft)?;
         types_builder.select(ft);
         let types = types_builder
             .build()
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-        walker.types(types);
+        if cli.type_shebang {
+            // The type matcher would drop extensionless scripts before we
+            // could look inside them, so apply it by hand instead.
+            let ft = ft.clone();
+            entry_filters.push(Box::new(move |entry| {
+                if entry.file_type().is_none_or(|t| t.is_dir()) {
+                    return true;
+                }
+                types.matched(entry.path(), false).is_whitelist()
+                    || (entry.path().extension().is_none()
+                        && shebang_type(entry.path()).is_some_and(|t| t == ft))
+            }));
+        } else {
+            walker.types(types);
+        }
+    }
+
+    if !entry_filters.is_empty() {
+        walker.filter_entry(move |entry| entry_filters.iter().all(|keep| keep(entry)));
     }
 
-    Ok(walker.build())
+    Ok(walker)
+}
+
+/// Directories excluded wholesale by a `dir/**` exclusion (`-g '!dir/**'` or
+/// `-x 'dir/**'`).
+///
+/// Such a glob matches everything below `dir` but not `dir` itself, so the
+/// override alone would still read the directory and drop its entries one by
+/// one. Matching the `dir` part against directories prunes it instead.
+fn pruned_dirs(cli: &Cli) -> io::Result<Option<ignore::overrides::Override>> {
+    let negated_glob = cli.glob.as_deref().and_then(|g| g.strip_prefix('!'));
+    let dirs: Vec<&str> = negated_glob
+        .into_iter()
+        .chain(cli.exclude.iter().map(String::as_str))
+        .filter_map(|pattern| pattern.strip_suffix("/**"))
+        .filter(|dir| !dir.is_empty())
+        .collect();
+    if dirs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = ignore::overrides::OverrideBuilder::new(&cli.path);
+    for dir in dirs {
+        // Like the glob, a bare name is anchored at the search path rather
+        // than matching a directory of that name at any depth.
+        let anchor = if dir.starts_with("**/") || dir.starts_with('/') { "" } else { "/" };
+        builder
+            .add(&format!("!{anchor}{dir}"))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Classify a file by its `#!` line, e.g. `#!/usr/bin/env python3` is `py`.
+/// Returns `None` if the file has no shebang or can't be read.
+fn shebang_type(path: &Path) -> Option<&'static str> {
+    use std::io::Read;
+
+    let mut head = [0u8; 128];
+    let n = std::fs::File::open(path).ok()?.read(&mut head).ok()?;
+    let first_line = head[..n].split(|&b| b == b'\n').next()?;
+    let first_line = std::str::from_utf8(first_line).ok()?;
+    interpreter_type(shebang_interpreter(first_line)?)
+}
+
+/// Extract the interpreter name from a shebang line, looking through
+/// `/usr/bin/env` and dropping version suffixes (`python3.12` -> `python`).
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+}
+
+/// Map an interpreter name to the file type name used by `-t`.
+fn interpreter_type(interpreter: &str) -> Option<&'static str> {
+    let file_type = match interpreter {
+        "sh" | "bash" | "dash" | "ksh" | "zsh" => "sh",
+        "python" => "py",
+        "perl" => "perl",
+        "ruby" => "ruby",
+        "node" | "nodejs" => "js",
+        "lua" => "lua",
+        "php" => "php",
+        "fish" => "fish",
+        "tclsh" | "wish" => "tcl",
+        "awk" | "gawk" => "awk",
+        _ => return None,
+    };
+    Some(file_type)
 }
 
 /// Prepare the regex pattern based on CLI flags (-F escapes, -w adds \b).
+///
+/// With --no-unicode-boundaries, -w uses ASCII word boundaries, which are
+/// cheaper but treat non-ASCII letters as non-word characters.
 pub(crate) fn prepare_regex_pattern(cli: &Cli) -> String {
-    let mut pattern = cli.pattern.clone().expect("pattern is required");
-    if cli.fixed_strings {
-        pattern = regex::escape(&pattern);
-    }
+    let boundary = if cli.no_unicode_boundaries {
+        r"(?-u:\b)"
+    } else {
+        r"\b"
+    };
+    word_pattern(cli, boundary)
+}
+
+/// Prepare the pattern for `git log -E`. Git's regex syntax has no `(?-u:)`
+/// groups, so -w always uses a plain `\b`.
+pub(crate) fn prepare_git_pattern(cli: &Cli) -> String {
+    word_pattern(cli, r"\b")
+}
+
+fn word_pattern(cli: &Cli, boundary: &str) -> String {
+    let escape = |pattern: &str| {
+        if cli.fixed_strings {
+            regex::escape(pattern)
+        } else {
+            pattern.to_string()
+        }
+    };
+    // --patterns-from patterns are alternatives. Plain groups rather than
+    // `(?:)`, which git's ERE syntax lacks.
+    let mut pattern = if cli.patterns.is_empty() {
+        escape(cli.pattern.as_deref().expect("pattern is required"))
+    } else {
+        let alternatives: Vec<String> = cli.patterns.iter().map(|p| escape(p)).collect();
+        format!("({})", alternatives.join("|"))
+    };
     if cli.word_regexp {
-        pattern = format!(r"\b{pattern}\b");
+        pattern = format!("{boundary}{pattern}{boundary}");
     }
     pattern
 }
 
-pub(crate) fn search_names(cli: &Cli) -> io::Result<Vec<String>> {
+pub(crate) fn search_names(cli: &Cli, ignore_case: bool) -> io::Result<Vec<String>> {
+    if cli.xattr.is_some() && !cfg!(any(target_os = "linux", target_os = "macos")) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--xattr is only supported on Linux and macOS",
+        ));
+    }
     let mut matches = Vec::new();
     let pattern = prepare_regex_pattern(cli);
     let re = regex::RegexBuilder::new(&pattern)
-        .case_insensitive(cli.ignore_case)
+        .case_insensitive(ignore_case)
         .build()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
-    for entry in build_walker(cli)? {
+    for_each_entry(cli, cli.include_dirs, |path, is_dir| {
+        // With --canonicalize, match against the absolute path (one extra
+        // syscall per file) but still report the path as walked.
+        let candidate = if cli.canonicalize {
+            match path.canonicalize() {
+                Ok(canonical) => canonical.to_string_lossy().into_owned(),
+                Err(err) => return skip_or_fail(cli, format!("{}: {err}", path.display())),
+            }
+        } else {
+            path.to_string_lossy().into_owned()
+        };
+        // Patterns use `/` on every platform unless --native-separators.
+        let matched = if cli.native_separators {
+            re.is_match(&candidate)
+        } else {
+            re.is_match(&with_forward_slashes(&candidate, std::path::MAIN_SEPARATOR))
+        };
+        let has_xattr = match cli.xattr {
+            Some((ref name, ref value)) => has_xattr(path, name, value.as_deref()),
+            None => true,
+        };
+        if matched && has_xattr {
+            // A trailing slash marks directory matches.
+            let mut display = path.display().to_string();
+            if is_dir && !display.ends_with('/') {
+                display.push('/');
+            }
+            matches.push(display);
+        }
+        Ok(())
+    })?;
+
+    Ok(matches)
+}
+
+/// Replace `separator` with `/`, so name patterns written with `/` match on
+/// Windows too. A no-op where the separator already is `/`.
+fn with_forward_slashes(path: &str, separator: char) -> Cow<'_, str> {
+    if separator == '/' || !path.contains(separator) {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(path.replace(separator, "/"))
+    }
+}
+
+/// Whether `path` has extended attribute `name`, with exactly `value` if one
+/// is given. Unreadable attributes count as missing.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn has_xattr(path: &Path, name: &str, value: Option<&str>) -> bool {
+    let Ok(len) = rustix::fs::getxattr(path, name, &mut [0u8; 0]) else {
+        return false;
+    };
+    let Some(value) = value else {
+        return true;
+    };
+    let mut buf = vec![0u8; len];
+    match rustix::fs::getxattr(path, name, &mut buf[..]) {
+        Ok(len) => &buf[..len] == value.as_bytes(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn has_xattr(_path: &Path, _name: &str, _value: Option<&str>) -> bool {
+    false
+}
+
+/// Reject a `-t` type that isn't defined, suggesting the closest known name,
+/// instead of letting the walker silently match nothing.
+fn check_file_type(types: &ignore::types::TypesBuilder, file_type: &str) -> io::Result<()> {
+    let definitions = types.definitions();
+    if file_type == "all" || definitions.iter().any(|d| d.name() == file_type) {
+        return Ok(());
+    }
+    let suggestion = definitions
+        .iter()
+        .map(|d| (edit_distance(file_type, d.name()), d.name()))
+        .min()
+        .filter(|&(distance, _)| distance <= 2)
+        .map(|(_, name)| format!("; did you mean `{name}`?"))
+        .unwrap_or_default();
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unknown file type `{file_type}`{suggestion}"),
+    ))
+}
+
+/// Levenshtein distance between two strings, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            row.push(substitute.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// Call `visit` with every file to search. A search path that names a file is
+/// visited directly without building a walker, so ignore rules and filters
+/// never hide a file the user asked for explicitly (as in ripgrep).
+///
+/// An error from `visit` ends the walk; see [`skip_or_fail`].
+fn for_each_file(cli: &Cli, mut visit: impl FnMut(&Path) -> io::Result<()>) -> io::Result<()> {
+    for_each_entry(cli, false, |path, _| visit(path))
+}
+
+/// Like [`for_each_file`], but with `dirs` set, directories (including the
+/// search root) are visited too. `visit` is told whether the path is one.
+pub(crate) fn for_each_entry(
+    cli: &Cli,
+    dirs: bool,
+    mut visit: impl FnMut(&Path, bool) -> io::Result<()>,
+) -> io::Result<()> {
+    let root = Path::new(&cli.path);
+    if root.is_file() {
+        return visit(root, false);
+    }
+
+    for entry in build_walker(cli)?.build() {
         let entry = match entry {
             Ok(e) => e,
             Err(err) => {
-                eprintln!("qro: {err}");
+                skip_or_fail(cli, err.to_string())?;
                 continue;
             }
         };
 
-        if entry.path().is_dir() {
+        let is_dir = entry.path().is_dir();
+        if is_dir && !dirs {
             continue;
         }
 
-        let path = entry.path();
-        if re.is_match(&path.to_string_lossy()) {
-            matches.push(path.display().to_string());
-        }
+        visit(entry.path(), is_dir)?;
     }
 
-    Ok(matches)
+    Ok(())
+}
+
+/// Like [`for_each_file`], but walking and visiting files on `threads`
+/// threads at once (0 picks a number from the available parallelism).
+/// `make_visitor` is called once per thread, so each visitor can keep its own
+/// state, and files reach them in no particular order.
+///
+/// The first error from a visitor stops every thread and is returned.
+fn for_each_file_parallel<'s, F>(
+    cli: &'s Cli,
+    threads: usize,
+    mut make_visitor: impl FnMut() -> F,
+) -> io::Result<()>
+where
+    F: FnMut(&Path) -> io::Result<()> + Send + 's,
+{
+    let root = Path::new(&cli.path);
+    if root.is_file() {
+        return make_visitor()(root);
+    }
+
+    let first_error: Mutex<Option<io::Error>> = Mutex::new(None);
+    build_walker(cli)?.threads(threads).build_parallel().run(|| {
+        let mut visit = make_visitor();
+        let first_error = &first_error;
+        Box::new(move |entry| {
+            let result = match entry {
+                Ok(entry) if entry.path().is_dir() => Ok(()),
+                Ok(entry) => visit(entry.path()),
+                Err(err) => skip_or_fail(cli, err.to_string()),
+            };
+            match result {
+                Ok(()) => WalkState::Continue,
+                Err(err) => {
+                    first_error.lock().unwrap().get_or_insert(err);
+                    WalkState::Quit
+                }
+            }
+        })
+    });
+
+    match first_error.into_inner().unwrap() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Report an error with one file or directory and carry on with the rest,
+/// or with `--fail-fast-on-error`, return it to end the search.
+fn skip_or_fail(cli: &Cli, message: String) -> io::Result<()> {
+    if cli.fail_fast_on_error {
+        return Err(io::Error::other(message));
+    }
+    eprintln!("qro: {message}");
+    Ok(())
 }
 
 /// Suggest a corrected pattern when a regex parse error occurs.
@@ -178,65 +711,565 @@ fn fix_brace_alternation(pattern: &str) -> Option<String> {
     if found { Some(result) } else { None }
 }
 
-pub(crate) fn search_content(cli: &Cli) -> io::Result<BTreeMap<String, Vec<ContentMatch>>> {
-    let pattern = prepare_regex_pattern(cli);
+pub(crate) fn search_content(
+    cli: &Cli,
+    ignore_case: bool,
+) -> io::Result<BTreeMap<String, Vec<ContentMatch>>> {
+    if cli.hex {
+        return search_hex(cli);
+    }
+
+    let line_glob = if cli.line_glob {
+        let glob = GlobBuilder::new(cli.pattern.as_deref().expect("pattern is required"))
+            .case_insensitive(ignore_case)
+            .literal_separator(false)
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Some(glob.compile_matcher())
+    } else {
+        None
+    };
+    // The glob does the matching, so the searcher reports every line.
+    let pattern = if line_glob.is_some() {
+        String::new()
+    } else {
+        prepare_regex_pattern(cli)
+    };
+    // With CRs stripped from the output, `$` should also match before a CRLF
+    // line ending, as if the CR weren't there.
     let matcher = RegexMatcherBuilder::new()
-        .case_insensitive(cli.ignore_case)
+        .case_insensitive(ignore_case)
+        .crlf(!cli.keep_cr)
         .build(&pattern)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
-    let mut searcher = SearcherBuilder::new()
+    // A UTF-8/UTF-16 BOM is always honoured. Otherwise files are read as
+    // UTF-8, as the given --encoding, or with `auto`, as Windows-1252 (a
+    // latin1 superset) when they aren't valid UTF-8.
+    let auto = cli.encoding.as_deref() == Some("auto");
+    let encoding = match cli.encoding.as_deref() {
+        None | Some("auto") => None,
+        Some(label) => Some(
+            Encoding::new(label).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        ),
+    };
+    let binary_hex_re = if cli.binary_hex {
+        Some(
+            regex::bytes::RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        )
+    } else {
+        None
+    };
+
+    let slurp_re = if cli.slurp {
+        Some(
+            regex::bytes::RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .multi_line(true)
+                .crlf(!cli.keep_cr)
+                .build()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        )
+    } else {
+        None
+    };
+
+    let results: Mutex<BTreeMap<String, Vec<ContentMatch>>> = Mutex::new(BTreeMap::new());
+
+    // One visitor per walker thread, each with its own searchers.
+    let make_visitor = || {
+        let mut searcher = build_searcher(encoding.clone(), cli);
+        let mut latin1_searcher = if auto {
+            Some(build_searcher(Encoding::new("windows-1252").ok(), cli))
+        } else {
+            None
+        };
+        let (matcher, line_glob, binary_hex_re, slurp_re, results) =
+            (&matcher, &line_glob, &binary_hex_re, &slurp_re, &results);
+        move |path: &Path| -> io::Result<()> {
+            let path_str = path.display().to_string();
+
+            // --max-line-length is checked as the file is read for the
+            // search, which fails with `LineTooLong` at the first long line.
+            let skip_long_lines = || {
+                if cli.verbose
+                    && let Some(max) = cli.max_line_length
+                {
+                    eprintln!("qro: {path_str}: skipped, has a line over {max} bytes");
+                }
+                Ok(())
+            };
+
+            if let Some(re) = slurp_re {
+                match slurp_file(re, path, cli.line_range, !cli.keep_cr, cli.max_line_length) {
+                    Ok(matches) if !matches.is_empty() => {
+                        results.lock().unwrap().insert(path_str, matches);
+                    }
+                    Ok(_) => {}
+                    Err(err) if is_line_too_long(&err) => skip_long_lines()?,
+                    Err(err) => skip_or_fail(cli, format!("{path_str}: {err}"))?,
+                }
+                return Ok(());
+            }
+
+            let mut sink = ContentSink {
+                matches: Vec::new(),
+                saw_binary: false,
+                line_range: cli.line_range,
+                // --json gives lines as stored, like ripgrep, so its text
+                // and offsets match ripgrep's for CRLF files.
+                strip_cr: !cli.keep_cr && !cli.json,
+                line_glob: line_glob.clone(),
+                submatcher: (cli.json || cli.count_matches).then(|| matcher.clone()),
+            };
+            let searcher = match latin1_searcher {
+                Some(ref mut latin1) if !looks_like_utf8(path) => latin1,
+                _ => &mut searcher,
+            };
+            let result = match cli.max_line_length {
+                // A long line past the end of --line-range goes unseen, as
+                // the search stops there.
+                Some(max) => std::fs::File::open(path).and_then(|file| {
+                    let reader = LineLengthLimit { inner: file, max, line_len: 0 };
+                    searcher.search_reader(matcher, reader, &mut sink)
+                }),
+                None => searcher.search_path(matcher, path, &mut sink),
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(err) if is_line_too_long(&err) => return skip_long_lines(),
+                Err(err) => return skip_or_fail(cli, format!("{path_str}: {err}")),
+            }
+            // Matches outside --line-range leave their context behind.
+            retain_context_near_matches(&mut sink.matches, cli.lines_before(), cli.lines_after());
+
+            if sink.saw_binary
+                && let Some(re) = binary_hex_re
+            {
+                // The searcher stopped at the first NUL byte, so look for
+                // matches anywhere in the file, not just before it.
+                match binary_hex_windows(re, path) {
+                    Ok(windows) if !windows.is_empty() => {
+                        results.lock().unwrap().insert(path_str, windows);
+                    }
+                    Ok(_) => {}
+                    Err(err) => skip_or_fail(cli, format!("{path_str}: {err}"))?,
+                }
+            } else if sink.saw_binary && !sink.matches.is_empty() {
+                // File had real matches before binary data was detected.
+                // Drop the raw lines and show a summary instead.
+                results
+                    .lock()
+                    .unwrap()
+                    .entry(path_str)
+                    .or_default()
+                    .push(ContentMatch::BinaryFile);
+            } else if !sink.matches.is_empty() {
+                // The searcher reports lines in file order already; sorting makes
+                // that an explicit guarantee rather than an accident of the sink.
+                // A stable sort keeps it cheap for the already-sorted case.
+                sink.matches.sort_by_key(ContentMatch::sort_key);
+                results.lock().unwrap().insert(path_str, sink.matches);
+            }
+            Ok(())
+        }
+    };
+
+    // Results are keyed by path, so they come out in the same order however
+    // many threads found them.
+    if cli.threads == 1 {
+        for_each_file(cli, make_visitor())?;
+    } else {
+        for_each_file_parallel(cli, cli.threads, make_visitor)?;
+    }
+
+    Ok(results.into_inner().unwrap())
+}
+
+fn build_searcher(encoding: Option<Encoding>, cli: &Cli) -> Searcher {
+    let terminator = if !cli.keep_cr {
+        LineTerminator::crlf()
+    } else {
+        LineTerminator::byte(b'\n')
+    };
+    SearcherBuilder::new()
         .line_number(true)
+        .line_terminator(terminator)
         .binary_detection(BinaryDetection::quit(b'\x00'))
-        .build();
+        .encoding(encoding)
+        .before_context(cli.lines_before() as usize)
+        .after_context(cli.lines_after() as usize)
+        .build()
+}
 
-    let mut results: BTreeMap<String, Vec<ContentMatch>> = BTreeMap::new();
+/// The error a [`LineLengthLimit`] fails with at a line longer than its limit.
+#[derive(Debug)]
+struct LineTooLong;
 
-    for entry in build_walker(cli)? {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(err) => {
-                eprintln!("qro: {err}");
-                continue;
+impl std::fmt::Display for LineTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("line too long")
+    }
+}
+
+impl std::error::Error for LineTooLong {}
+
+fn is_line_too_long(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|e| e.is::<LineTooLong>())
+}
+
+/// A reader that fails with [`LineTooLong`] once a line runs past `max`
+/// bytes (`--max-line-length`).
+struct LineLengthLimit<R> {
+    inner: R,
+    max: u64,
+    /// Length of the line read so far.
+    line_len: u64,
+}
+
+impl<R: Read> Read for LineLengthLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for &byte in &buf[..n] {
+            if byte == b'\n' {
+                self.line_len = 0;
+            } else {
+                self.line_len += 1;
+                if self.line_len > self.max {
+                    return Err(io::Error::other(LineTooLong));
+                }
             }
-        };
+        }
+        Ok(n)
+    }
+}
 
-        if entry.path().is_dir() {
-            continue;
+/// Read a file decoded as the content search reads it: by its BOM if it
+/// has one, else as `--encoding` (with `auto`, Windows-1252 unless it looks
+/// like UTF-8), transcoded to UTF-8.
+pub(crate) fn read_decoded(path: &Path, cli: &Cli) -> io::Result<Vec<u8>> {
+    let label = match cli.encoding.as_deref() {
+        Some("auto") if !looks_like_utf8(path) => Some("windows-1252"),
+        None | Some("auto") => None,
+        label => label,
+    };
+    let encoding = label.and_then(|l| encoding_rs::Encoding::for_label_no_replacement(l.as_bytes()));
+    let mut text = Vec::new();
+    DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .utf8_passthru(true)
+        .strip_bom(true)
+        .bom_override(true)
+        .bom_sniffing(true)
+        .build(std::fs::File::open(path)?)
+        .read_to_end(&mut text)?;
+    Ok(text)
+}
+
+/// Guess whether a file is UTF-8 from its first 64 KiB, for `--encoding auto`.
+///
+/// Files with a BOM or a NUL byte count as UTF-8 here, leaving them to the
+/// searcher's BOM sniffing and binary detection. So does a file that can't be
+/// read; the search itself will report the error.
+fn looks_like_utf8(path: &Path) -> bool {
+    let mut head = Vec::new();
+    let read = std::fs::File::open(path).and_then(|f| f.take(64 * 1024).read_to_end(&mut head));
+    if read.is_err() {
+        return true;
+    }
+    if head.starts_with(b"\xEF\xBB\xBF")
+        || head.starts_with(b"\xFF\xFE")
+        || head.starts_with(b"\xFE\xFF")
+        || head.contains(&0)
+    {
+        return true;
+    }
+    match std::str::from_utf8(&head) {
+        Ok(_) => true,
+        // Cut off mid-character at the end of the sample.
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+/// The first line in the first 64 KiB of a file that `re` matches, or failing
+/// that, its first line (`--preview`). `None` for empty, binary or unreadable
+/// files and directories.
+pub(crate) fn preview_line(path: &Path, re: &regex::Regex) -> Option<(u64, String)> {
+    let mut head = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|f| f.take(64 * 1024).read_to_end(&mut head))
+        .ok()?;
+    if head.contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&head);
+    let mut lines = text.lines().zip(1..);
+    let (line, line_number) = lines
+        .clone()
+        .find(|(line, _)| re.is_match(line))
+        .or_else(|| lines.next())?;
+    Some((line_number, line.to_string()))
+}
+
+/// Group the files among `paths` whose contents are identical
+/// (`--find-duplicates`). Returns each group's file size and paths, in path
+/// order. Empty files, directories and unreadable files are left out.
+///
+/// Only files of equal size are read and hashed, and files with equal hashes
+/// are compared byte for byte before they're grouped.
+pub(crate) fn duplicate_groups<'a>(
+    paths: impl Iterator<Item = &'a str>,
+) -> Vec<(u64, Vec<String>)> {
+    let mut by_size: BTreeMap<u64, Vec<&str>> = BTreeMap::new();
+    for path in paths {
+        if let Ok(meta) = std::fs::metadata(path)
+            && meta.is_file()
+            && meta.len() > 0
+        {
+            by_size.entry(meta.len()).or_default().push(path);
         }
+    }
 
-        let path = entry.path().to_path_buf();
-        let path_str = path.display().to_string();
-        let mut sink = ContentSink {
-            matches: Vec::new(),
-            saw_binary: false,
-        };
-        let result = searcher.search_path(&matcher, &path, &mut sink);
+    let mut groups = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        // Sets of identical files by hash. A set's first file stands in for
+        // the rest when another file is compared against it.
+        let mut by_hash: HashMap<u64, Vec<Vec<String>>> = HashMap::new();
+        for path in paths {
+            let placed = hash_file(path).and_then(|hash| {
+                let sets = by_hash.entry(hash).or_default();
+                for same in sets.iter_mut() {
+                    if same_contents(&same[0], path)? {
+                        same.push(path.to_string());
+                        return Ok(());
+                    }
+                }
+                sets.push(vec![path.to_string()]);
+                Ok(())
+            });
+            if let Err(err) = placed {
+                eprintln!("qro: {path}: {err}");
+            }
+        }
+        groups.extend(
+            by_hash
+                .into_values()
+                .flatten()
+                .filter(|same| same.len() > 1)
+                .map(|mut same| {
+                    same.sort();
+                    (size, same)
+                }),
+        );
+    }
+    groups.sort_by(|a, b| a.1.cmp(&b.1));
+    groups
+}
 
-        if let Err(err) = result {
-            eprintln!("qro: {}: {err}", path.display());
-            continue;
+/// Hash a file's contents, reading it a buffer at a time.
+fn hash_file(path: &str) -> io::Result<u64> {
+    let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+    let mut hasher = DefaultHasher::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(hasher.finish());
         }
+        hasher.write(buf);
+        let consumed = buf.len();
+        reader.consume(consumed);
+    }
+}
 
-        if sink.saw_binary && !sink.matches.is_empty() {
-            // File had real matches before binary data was detected.
-            // Drop the raw lines and show a summary instead.
-            results
-                .entry(path_str)
-                .or_default()
-                .push(ContentMatch::BinaryFile);
-        } else if !sink.matches.is_empty() {
-            results.insert(path_str, sink.matches);
+/// Whether two files have the same contents, compared a buffer at a time.
+fn same_contents(a: &str, b: &str) -> io::Result<bool> {
+    let mut a = io::BufReader::new(std::fs::File::open(a)?);
+    let mut b = io::BufReader::new(std::fs::File::open(b)?);
+    loop {
+        let (a_buf, b_buf) = (a.fill_buf()?, b.fill_buf()?);
+        if a_buf.is_empty() || b_buf.is_empty() {
+            return Ok(a_buf.is_empty() && b_buf.is_empty());
         }
+        let n = a_buf.len().min(b_buf.len());
+        if a_buf[..n] != b_buf[..n] {
+            return Ok(false);
+        }
+        a.consume(n);
+        b.consume(n);
+    }
+}
+
+/// Parse a `--hex` pattern such as `deadbeef` or `de ad be ef` into bytes.
+fn parse_hex(pattern: &str) -> io::Result<Vec<u8>> {
+    let invalid = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid hex pattern `{pattern}`: {reason}"),
+        )
+    };
+    let digits: Vec<u8> = pattern
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    if digits.is_empty() {
+        return Err(invalid("no bytes given"));
     }
+    if !digits.len().is_multiple_of(2) {
+        return Err(invalid("odd number of hex digits"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| invalid("expected hex digits 0-9 and a-f"))
+        })
+        .collect()
+}
+
+/// Search raw file bytes for the `--hex` byte sequence. There's no line
+/// splitting and no binary detection; each match is reported by offset.
+fn search_hex(cli: &Cli) -> io::Result<BTreeMap<String, Vec<ContentMatch>>> {
+    let needle = parse_hex(cli.pattern.as_deref().expect("pattern is required"))?;
+    let pattern: String = needle.iter().map(|b| format!(r"\x{b:02x}")).collect();
+    let re = regex::bytes::RegexBuilder::new(&pattern)
+        .unicode(false)
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut results = BTreeMap::new();
+    for_each_file(cli, |path| match std::fs::read(path) {
+        Ok(bytes) => {
+            let offsets: Vec<ContentMatch> = re
+                .find_iter(&bytes)
+                .map(|m| ContentMatch::Offset(m.start() as u64))
+                .collect();
+            if !offsets.is_empty() {
+                results.insert(path.display().to_string(), offsets);
+            }
+            Ok(())
+        }
+        Err(err) => skip_or_fail(cli, format!("{}: {err}", path.display())),
+    })?;
 
     Ok(results)
 }
 
-/// Build regex patterns from `--dont-match` and `--filter-out` lists.
+/// Find every match in a binary file (`--binary-hex`) and dump the bytes
+/// around each one.
+fn binary_hex_windows(re: &regex::bytes::Regex, path: &Path) -> io::Result<Vec<ContentMatch>> {
+    let bytes = std::fs::read(path)?;
+    Ok(re
+        .find_iter(&bytes)
+        .map(|m| ContentMatch::HexDump {
+            offset: m.start() as u64,
+            rows: hex_window(&bytes, m.start(), m.end()),
+        })
+        .collect())
+}
+
+/// Bytes per row of a hex dump.
+const HEX_ROW: usize = 16;
+
+/// Dump the rows holding `bytes[start..end]`, plus one row either side, in
+/// `xxd` format: offset, hex in 2-byte groups, then the printable ASCII.
+/// Long matches are cut off after four rows.
+fn hex_window(bytes: &[u8], start: usize, end: usize) -> Vec<String> {
+    let first_row = (start / HEX_ROW).saturating_sub(1);
+    let last_match_row = (end.max(start + 1) - 1) / HEX_ROW;
+    let last_row = last_match_row.min(start / HEX_ROW + 3) + 1;
+
+    (first_row..=last_row)
+        .map(|row| row * HEX_ROW)
+        .take_while(|&row_start| row_start < bytes.len())
+        .map(|row_start| {
+            let row = &bytes[row_start..(row_start + HEX_ROW).min(bytes.len())];
+            let hex: Vec<String> = row
+                .chunks(2)
+                .map(|pair| pair.iter().map(|b| format!("{b:02x}")).collect())
+                .collect();
+            let ascii: String = row
+                .iter()
+                .map(|&b| if b == b' ' || b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            format!("{row_start:08x}: {:<39}  {ascii}", hex.join(" "))
+        })
+        .collect()
+}
+
+/// Search a whole file as a single buffer (`--slurp`), so a match may span
+/// lines. Every line a match touches is reported once, in line order.
+///
+/// The file is read fully into memory. Files containing a NUL byte are
+/// treated as binary and skipped.
+fn slurp_file(
+    re: &regex::bytes::Regex,
+    path: &Path,
+    line_range: Option<(u64, u64)>,
+    strip_cr: bool,
+    max_line_length: Option<u64>,
+) -> io::Result<Vec<ContentMatch>> {
+    let content = std::fs::read(path)?;
+    if content.contains(&0) {
+        return Ok(Vec::new());
+    }
+    if let Some(max) = max_line_length
+        && content.split(|&b| b == b'\n').any(|line| line.len() as u64 > max)
+    {
+        return Err(io::Error::other(LineTooLong));
+    }
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            content
+                .iter()
+                .enumerate()
+                .filter(|&(_, &b)| b == b'\n')
+                .map(|(i, _)| i + 1),
+        )
+        .collect();
+    // 1-based line number containing byte `offset`.
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let mut matches = Vec::new();
+    let mut last_reported = 0;
+    for mat in re.find_iter(&content) {
+        let first = line_of(mat.start());
+        let last = line_of(mat.end().saturating_sub(1).max(mat.start()));
+        for n in first.max(last_reported + 1)..=last {
+            let in_range =
+                line_range.is_none_or(|(start, end)| (start..=end).contains(&(n as u64)));
+            if !in_range {
+                continue;
+            }
+            let start = line_starts[n - 1];
+            let end = line_starts.get(n).copied().unwrap_or(content.len());
+            let line = line_text(&content[start..end], strip_cr);
+            matches.push(ContentMatch::Line {
+                line_number: n as u64,
+                line,
+                offset: start as u64,
+                newline: content[start..end].ends_with(b"\n"),
+                submatches: Vec::new(),
+            });
+        }
+        last_reported = last_reported.max(last);
+    }
+
+    Ok(matches)
+}
+
+/// Build regex patterns from `--dont-match` and `--filter-out` lists, with
+/// the case rule of the pass they filter.
 /// Returns `(dont_match_regexes, filter_out_regexes)`.
 pub(crate) fn build_exclude_regexes(
     cli: &Cli,
+    ignore_case: bool,
 ) -> io::Result<(Vec<regex::Regex>, Vec<regex::Regex>)> {
     let build = |patterns: &[String]| -> io::Result<Vec<regex::Regex>> {
         patterns
@@ -248,7 +1281,7 @@ pub(crate) fn build_exclude_regexes(
                     p.clone()
                 };
                 regex::RegexBuilder::new(&escaped)
-                    .case_insensitive(cli.ignore_case)
+                    .case_insensitive(ignore_case)
                     .build()
                     .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
             })
@@ -318,7 +1351,10 @@ pub(crate) fn filter_content_matches(
                     ContentMatch::Line { line, .. } => {
                         should_keep(line, search_re, dont_match, filter_out)
                     }
-                    ContentMatch::BinaryFile => true,
+                    ContentMatch::Context { .. }
+                    | ContentMatch::BinaryFile
+                    | ContentMatch::Offset(_)
+                    | ContentMatch::HexDump { .. } => true,
                 })
                 .collect();
             if filtered.is_empty() {
@@ -330,6 +1366,88 @@ pub(crate) fn filter_content_matches(
         .collect()
 }
 
+/// Drop context lines that no longer surround a matching line, after a
+/// filter has removed the match they were shown for.
+fn retain_context_near_matches(matches: &mut Vec<ContentMatch>, before: u64, after: u64) {
+    let mut match_lines: Vec<u64> = matches
+        .iter()
+        .filter_map(|m| match m {
+            ContentMatch::Line { line_number, .. } => Some(*line_number),
+            _ => None,
+        })
+        .collect();
+    match_lines.sort_unstable();
+    matches.retain(|m| {
+        let ContentMatch::Context { line_number, .. } = m else {
+            return true;
+        };
+        // Some match must lie in `line_number - after ..= line_number + before`.
+        let first = match_lines.partition_point(|&l| l < line_number.saturating_sub(after));
+        match_lines
+            .get(first)
+            .is_some_and(|&l| l <= line_number.saturating_add(before))
+    });
+}
+
+/// Drop context lines left without a match nearby by the filters that run
+/// after the search, and files left with only context lines.
+pub(crate) fn drop_stray_context(
+    matches: BTreeMap<String, Vec<ContentMatch>>,
+    before: u64,
+    after: u64,
+) -> BTreeMap<String, Vec<ContentMatch>> {
+    matches
+        .into_iter()
+        .filter_map(|(path, mut content_matches)| {
+            retain_context_near_matches(&mut content_matches, before, after);
+            if content_matches
+                .iter()
+                .all(|m| matches!(m, ContentMatch::Context { .. }))
+            {
+                None
+            } else {
+                Some((path, content_matches))
+            }
+        })
+        .collect()
+}
+
+/// Drop repeated matching lines for `--dedup`, keeping the first occurrence
+/// (and so its line number). Files left with no matches are dropped.
+pub(crate) fn dedup_content_matches(
+    matches: BTreeMap<String, Vec<ContentMatch>>,
+    scope: Dedup,
+) -> BTreeMap<String, Vec<ContentMatch>> {
+    let mut seen_anywhere: HashSet<String> = HashSet::new();
+    matches
+        .into_iter()
+        .filter_map(|(path, content_matches)| {
+            let mut seen_in_file: HashSet<String> = HashSet::new();
+            let mut previous: Option<String> = None;
+            let kept: Vec<ContentMatch> = content_matches
+                .into_iter()
+                .filter(|m| {
+                    let ContentMatch::Line { line, .. } = m else {
+                        return true;
+                    };
+                    match scope {
+                        Dedup::Consecutive => {
+                            previous.replace(line.clone()).as_ref() != Some(line)
+                        }
+                        Dedup::File => seen_in_file.insert(line.clone()),
+                        Dedup::All => seen_anywhere.insert(line.clone()),
+                    }
+                })
+                .collect();
+            if kept.is_empty() {
+                None
+            } else {
+                Some((path, kept))
+            }
+        })
+        .collect()
+}
+
 /// Filter filename matches using dont_match and filter_out patterns.
 pub(crate) fn filter_name_matches(
     matches: Vec<String>,
@@ -347,6 +1465,47 @@ pub(crate) fn filter_name_matches(
 mod tests {
     use super::*;
 
+    #[test]
+    fn hex_window_shows_a_row_of_context_either_side() {
+        let mut bytes = vec![0u8; 64];
+        bytes[20..25].copy_from_slice(b"hello");
+
+        assert_eq!(
+            hex_window(&bytes, 20, 25),
+            [
+                "00000000: 0000 0000 0000 0000 0000 0000 0000 0000  ................",
+                "00000010: 0000 0000 6865 6c6c 6f00 0000 0000 0000  ....hello.......",
+                "00000020: 0000 0000 0000 0000 0000 0000 0000 0000  ................",
+            ]
+        );
+        assert_eq!(hex_window(b"hi", 0, 2), ["00000000: 6869                                     hi"]);
+    }
+
+    #[test]
+    fn parse_hex_accepts_spaced_and_mixed_case_digits() {
+        assert_eq!(parse_hex("deadBEEF").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(parse_hex("00 ff").unwrap(), [0x00, 0xff]);
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+        assert!(parse_hex(" ").is_err());
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("rust", "rust"), 0);
+        assert_eq!(edit_distance("rst", "rust"), 1);
+        assert_eq!(edit_distance("pyhton", "python"), 2);
+        assert_eq!(edit_distance("", "go"), 2);
+    }
+
+    #[test]
+    fn forward_slashes_for_name_matching() {
+        assert_eq!(with_forward_slashes(r"src\main.rs", '\\'), "src/main.rs");
+        assert_eq!(with_forward_slashes("src/main.rs", '/'), "src/main.rs");
+        // On Unix a backslash is an ordinary file name character.
+        assert_eq!(with_forward_slashes(r"a\b", '/'), r"a\b");
+    }
+
     #[test]
     fn hint_brace_alternation() {
         let hint = regex_hint("{foo,bar}::baz").unwrap();
@@ -385,6 +1544,28 @@ mod tests {
         assert!(regex_hint(r"\bword\b").is_none());
     }
 
+    #[test]
+    fn shebang_interpreter_direct_and_env() {
+        assert_eq!(shebang_interpreter("#!/bin/bash"), Some("bash"));
+        assert_eq!(shebang_interpreter("#! /bin/sh -e"), Some("sh"));
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env python3"),
+            Some("python")
+        );
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env -S python3.12 -u"),
+            Some("python")
+        );
+        assert_eq!(shebang_interpreter("echo hi"), None);
+    }
+
+    #[test]
+    fn interpreter_maps_to_type_name() {
+        assert_eq!(interpreter_type("bash"), Some("sh"));
+        assert_eq!(interpreter_type("python"), Some("py"));
+        assert_eq!(interpreter_type("cobol"), None);
+    }
+
     #[test]
     fn no_hint_brace_without_comma() {
         // {3} is a valid regex repetition, not brace alternation
@@ -0,0 +1,265 @@
+//! A deliberately small lexer for `--in comments` and `--in strings`.
+//!
+//! It knows just enough about a few languages to tell comments and string
+//! literals from code: Rust, Python, and the C family (C, C++, Java,
+//! JavaScript, TypeScript, Go). Raw strings, heredocs and the like aren't
+//! understood, and files in other languages are skipped entirely.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::cli::{Cli, InContext};
+use crate::search::{read_decoded, ContentMatch};
+
+/// What a byte of source text is part of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Region {
+    Code,
+    Comment,
+    String,
+}
+
+/// The comment and string delimiters of one language.
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Whether block comments nest, as in Rust.
+    nested_blocks: bool,
+    /// String delimiters, longest first so `"""` wins over `"`.
+    quotes: &'static [&'static str],
+    /// Whether single-character quotes may span lines.
+    multiline_strings: bool,
+    /// Whether `'x'` and `'\n'` are char literals, where `'` otherwise
+    /// starts a lifetime or label.
+    char_literals: bool,
+}
+
+const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: true,
+    // `'` also starts lifetimes, so char literals are found separately.
+    quotes: &["\""],
+    multiline_strings: true,
+    char_literals: true,
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    nested_blocks: false,
+    quotes: &["\"\"\"", "'''", "\"", "'"],
+    multiline_strings: false,
+    char_literals: false,
+};
+
+const C_FAMILY: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &["\"", "'", "`"],
+    multiline_strings: false,
+    char_literals: false,
+};
+
+fn syntax_for(path: &Path) -> Option<&'static Syntax> {
+    let syntax = match path.extension()?.to_str()? {
+        "rs" => &RUST,
+        "py" | "pyi" => &PYTHON,
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "java" | "js" | "jsx" | "mjs" | "ts" | "tsx"
+        | "go" => &C_FAMILY,
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Classify every byte of `text` as code, comment or string.
+fn classify(text: &[u8], syntax: &Syntax) -> Vec<Region> {
+    let mut regions = vec![Region::Code; text.len()];
+    let at = |i: usize, token: &str| text[i..].starts_with(token.as_bytes());
+    let mut i = 0;
+
+    while i < text.len() {
+        if syntax.line_comments.iter().any(|c| at(i, c)) {
+            let end = text[i..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(text.len(), |n| i + n);
+            regions[i..end].fill(Region::Comment);
+            i = end;
+        } else if let Some((open, close)) = syntax.block_comment
+            && at(i, open)
+        {
+            let mut depth = 1;
+            let mut j = i + open.len();
+            while j < text.len() && depth > 0 {
+                if syntax.nested_blocks && at(j, open) {
+                    depth += 1;
+                    j += open.len();
+                } else if at(j, close) {
+                    depth -= 1;
+                    j += close.len();
+                } else {
+                    j += 1;
+                }
+            }
+            regions[i..j].fill(Region::Comment);
+            i = j;
+        } else if syntax.char_literals
+            && let Some(len) = char_literal_len(&text[i..])
+        {
+            regions[i..i + len].fill(Region::String);
+            i += len;
+        } else if let Some(quote) = syntax.quotes.iter().find(|q| at(i, q)) {
+            let mut j = i + quote.len();
+            while j < text.len() {
+                if text[j] == b'\\' {
+                    j += 2;
+                } else if at(j, quote) {
+                    j += quote.len();
+                    break;
+                } else if text[j] == b'\n' && quote.len() == 1 && !syntax.multiline_strings {
+                    break;
+                } else {
+                    j += 1;
+                }
+            }
+            let j = j.min(text.len());
+            regions[i..j].fill(Region::String);
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+
+    regions
+}
+
+/// The length of the char literal at the start of `text`, if there is one.
+///
+/// A `'` followed by one character and a closing `'`, or by an escape, is a
+/// char literal; anything else (`'a`, `'static`) is a lifetime or label.
+fn char_literal_len(text: &[u8]) -> Option<usize> {
+    let rest = text.strip_prefix(b"'")?;
+    if let Some(escape) = rest.strip_prefix(b"\\") {
+        // The escaped character, then up to `u{10FFFF}`'s worth more.
+        let close = escape.iter().skip(1).take(9).position(|&b| b == b'\'' || b == b'\n')?;
+        return (escape[close + 1] == b'\'').then_some(close + 4);
+    }
+    let width = match *rest.first()? {
+        b'\'' | b'\n' => return None,
+        b if b < 0x80 => 1,
+        b if b >= 0xF0 => 4,
+        b if b >= 0xE0 => 3,
+        _ => 2,
+    };
+    (rest.get(width) == Some(&b'\'')).then_some(width + 2)
+}
+
+/// Keep only the content matches where the pattern matches inside the
+/// requested context. Files in unsupported languages, and binary files, are
+/// dropped.
+pub(crate) fn filter_matches_in(
+    matches: BTreeMap<String, Vec<ContentMatch>>,
+    context: InContext,
+    re: &regex::bytes::Regex,
+    cli: &Cli,
+) -> BTreeMap<String, Vec<ContentMatch>> {
+    let wanted = match context {
+        InContext::Comments => Region::Comment,
+        InContext::Strings => Region::String,
+    };
+    matches
+        .into_iter()
+        .filter_map(|(path, content_matches)| {
+            let syntax = syntax_for(Path::new(&path))?;
+            // Decoded as the searcher did, so the line numbers agree.
+            let text = match read_decoded(Path::new(&path), cli) {
+                Ok(text) => text,
+                Err(err) => {
+                    eprintln!("qro: {path}: {err}");
+                    return None;
+                }
+            };
+            let regions = classify(&text, syntax);
+            let line_starts: Vec<usize> = std::iter::once(0)
+                .chain(
+                    text.iter()
+                        .enumerate()
+                        .filter(|(_, b)| **b == b'\n')
+                        .map(|(i, _)| i + 1),
+                )
+                .collect();
+
+            let kept: Vec<ContentMatch> = content_matches
+                .into_iter()
+                .filter(|m| {
+                    let line_number = match m {
+                        ContentMatch::Line { line_number, .. } => line_number,
+                        // Dropped later if its match goes.
+                        ContentMatch::Context { .. } => return true,
+                        _ => return false,
+                    };
+                    let index = *line_number as usize;
+                    let Some(&start) = index.checked_sub(1).and_then(|i| line_starts.get(i)) else {
+                        return false;
+                    };
+                    let end = line_starts.get(index).copied().unwrap_or(text.len());
+                    // An empty match can sit just past the last byte.
+                    re.find_iter(&text[start..end])
+                        .any(|mat| regions.get(start + mat.start()) == Some(&wanted))
+                })
+                .collect();
+            if kept.is_empty() {
+                None
+            } else {
+                Some((path, kept))
+            }
+        })
+        .collect()
+}
+
+/// Build the byte regex used to locate matches within a line.
+pub(crate) fn context_regex(pattern: &str, ignore_case: bool) -> io::Result<regex::bytes::Regex> {
+    regex::bytes::RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regions_of(text: &str, syntax: &Syntax) -> String {
+        classify(text.as_bytes(), syntax)
+            .iter()
+            .map(|r| match r {
+                Region::Code => '.',
+                Region::Comment => 'c',
+                Region::String => 's',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rust_comments_and_strings() {
+        assert_eq!(regions_of(r#"f("a\"b"); // x"#, &RUST), "..ssssss...cccc");
+        assert_eq!(regions_of("/* a /* b */ c */x", &RUST), "ccccccccccccccccc.");
+        assert_eq!(regions_of("'a: loop", &RUST), "........");
+    }
+
+    #[test]
+    fn rust_char_literals_are_strings() {
+        assert_eq!(regions_of(r#"b'"' // x"#, &RUST), ".sss.cccc");
+        assert_eq!(regions_of(r"'\'' '\u{1F600}'", &RUST), "ssss.sssssssssss");
+        assert_eq!(regions_of("f<'a>('é')", &RUST), "......ssss.");
+        assert_eq!(regions_of("&'static str", &RUST), "............");
+    }
+
+    #[test]
+    fn python_triple_quotes_span_lines() {
+        assert_eq!(regions_of("'''a\nb''' # c", &PYTHON), "sssssssss.ccc");
+    }
+}
@@ -41,6 +41,26 @@ fn make_git_repo(parent: &Path, name: &str, msg_marker: &str, file_content: &str
     repo
 }
 
+/// Run a git command in `repo` with a fixed identity, failing the test on error.
+fn git_in(repo: &Path, args: &[&str]) {
+    git_in_with_env(repo, args, &[]);
+}
+
+/// [`git_in`] with extra environment variables, e.g. `GIT_COMMITTER_DATE`.
+fn git_in_with_env(repo: &Path, args: &[&str], env: &[(&str, &str)]) {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@test")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@test")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {args:?} failed: {}", String::from_utf8_lossy(&out.stderr));
+}
+
 // --- Content search (-c) ---
 
 #[test]
@@ -388,6 +408,53 @@ fn no_log_disables_git_log() {
     assert!(!text.contains("(git log)"), "should not show git log section with --no-log");
 }
 
+#[test]
+fn log_flags_last_one_wins() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "repo-a", "Fix issue45701", "issue45701 in file");
+    let path = repo.to_str().unwrap();
+
+    // (flags, file results expected, git log expected)
+    let cases: [(&[&str], bool, bool); 5] = [
+        (&[], true, true),
+        (&["-l"], false, true),
+        (&["--no-log"], true, false),
+        (&["-l", "--no-log"], true, false),
+        (&["--no-log", "-l"], false, true),
+    ];
+    for (flags, files, log) in cases {
+        let mut args = flags.to_vec();
+        args.extend(["issue45701", path]);
+        let out = qro(&args);
+        let text = stdout(&out);
+
+        assert!(out.status.success(), "{flags:?}: stderr: {}", stderr(&out));
+        assert_eq!(text.contains("file.txt"), files, "{flags:?}: got: {text}");
+        assert_eq!(text.contains("(git log):"), log, "{flags:?}: got: {text}");
+    }
+}
+
+#[test]
+fn max_git_log_repos_caps_repos_searched() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in ["repo-a", "repo-b", "repo-c"] {
+        make_git_repo(tmp.path(), name, &format!("Fix issue45801 in {name}"), "unrelated");
+    }
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--log-only", "--max-git-log-repos", "2", "issue45801", path]);
+    let text = stdout(&out);
+
+    assert!(text.contains("in repo-a"), "got: {text}");
+    assert!(text.contains("in repo-b"), "got: {text}");
+    assert!(!text.contains("in repo-c"), "third repo is over the cap: {text}");
+    assert!(stderr(&out).contains("only searching the first 2"), "got: {}", stderr(&out));
+
+    let out = qro(&["--log-only", "--max-git-log-repos", "0", "issue45801", path]);
+    assert!(stdout(&out).contains("in repo-c"), "0 means no limit");
+    assert_eq!(stderr(&out), "");
+}
+
 #[test]
 fn no_git_repo_silently_skips_log() {
     let tmp = tempfile::tempdir().unwrap();
@@ -403,6 +470,38 @@ fn no_git_repo_silently_skips_log() {
     assert!(!text.contains("(git log)"), "should not show git log section");
 }
 
+#[test]
+fn require_git_fails_outside_any_repo() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("test.txt"), "hello45601").unwrap();
+
+    let out = qro(&["--require-git", "hello45601", tmp.path().to_str().unwrap()]);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stderr(&out).contains("not in a git repository"), "got: {}", stderr(&out));
+}
+
+#[test]
+fn require_git_passes_with_child_repo() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Fix issue45602", "unrelated");
+
+    let out = qro(&["--require-git", "--log-only", "issue45602", tmp.path().to_str().unwrap()]);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(stdout(&out).contains("issue45602"));
+}
+
+#[test]
+fn require_git_conflicts_with_modes_that_skip_the_log() {
+    for mode in ["--no-log", "--count", "--json", "--find-duplicates"] {
+        let out = qro(&["--require-git", mode, "pattern", "tests/fixtures/"]);
+
+        assert!(!out.status.success(), "{mode} should be rejected");
+        assert!(stderr(&out).contains("cannot be used with"), "{mode}: {}", stderr(&out));
+    }
+}
+
 #[test]
 fn log_discovers_child_repos() {
     let tmp = tempfile::tempdir().unwrap();
@@ -610,3 +709,2317 @@ fn no_ignore_flag_overrides_dot_ignore() {
 
     assert!(text.contains("data.log"), "--no-ignore should override .ignore");
 }
+
+fn make_two_ignore_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(tmp.path())
+        .output()
+        .unwrap();
+    fs::write(tmp.path().join(".gitignore"), "*.tmp\n").unwrap();
+    fs::write(tmp.path().join(".ignore"), "*.log\n").unwrap();
+    fs::write(tmp.path().join("scratch.tmp"), "findme").unwrap();
+    fs::write(tmp.path().join("data.log"), "findme").unwrap();
+    tmp
+}
+
+#[test]
+fn no_ignore_vcs_disables_gitignore_but_keeps_dot_ignore() {
+    let tmp = make_two_ignore_tree();
+
+    let out = qro(&["-c", "--no-ignore-vcs", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("scratch.tmp"), "--no-ignore-vcs should override .gitignore: {text}");
+    assert!(!text.contains("data.log"), ".ignore should still apply: {text}");
+}
+
+#[test]
+fn no_ignore_dot_disables_dot_ignore_but_keeps_gitignore() {
+    let tmp = make_two_ignore_tree();
+
+    let out = qro(&["-c", "--no-ignore-dot", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("data.log"), "--no-ignore-dot should override .ignore: {text}");
+    assert!(!text.contains("scratch.tmp"), ".gitignore should still apply: {text}");
+}
+
+// --- Line range (--line-range) ---
+
+#[test]
+fn line_range_excludes_matches_outside_window() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("gen.txt"),
+        "findme one\nother\nfindme three\nother\nfindme five\n",
+    )
+    .unwrap();
+
+    let out = qro(&["-c", "--line-range", "2:4", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success());
+    assert!(text.contains("3:findme three"), "should keep match inside range");
+    assert!(!text.contains("1:findme one"), "should drop match before range");
+    assert!(!text.contains("5:findme five"), "should drop match after range");
+}
+
+#[test]
+fn line_range_open_ended() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("gen.txt"), "findme one\nfindme two\nfindme three\n").unwrap();
+
+    let out = qro(&["-c", "--line-range", "2:", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(!text.contains("1:findme one"));
+    assert!(text.contains("2:findme two"));
+    assert!(text.contains("3:findme three"));
+}
+
+#[test]
+fn line_range_rejects_inverted_range() {
+    let out = qro(&["-c", "--line-range", "5:2", "findme", "tests/fixtures/"]);
+
+    assert!(!out.status.success(), "inverted range should fail");
+    assert!(stderr(&out).contains("start line 5 is after end line 2"));
+}
+
+// --- Stop-at directories (--stop-at) ---
+
+#[test]
+fn stop_at_prunes_named_directories() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("app.js"), "findme").unwrap();
+    let vendored = tmp.path().join("node_modules").join("left-pad");
+    fs::create_dir_all(&vendored).unwrap();
+    fs::write(vendored.join("index.js"), "findme").unwrap();
+    let nested = tmp.path().join("web").join("node_modules");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("lib.js"), "findme").unwrap();
+
+    let out = qro(&["-c", "--stop-at", "node_modules", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("app.js"), "should search files outside node_modules");
+    assert!(!text.contains("index.js"), "should not descend into top-level node_modules");
+    assert!(!text.contains("lib.js"), "should not descend into nested node_modules");
+}
+
+#[test]
+fn stop_at_does_not_prune_search_root() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("node_modules");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("index.js"), "findme").unwrap();
+
+    let out = qro(&["-c", "--stop-at", "node_modules", "findme", root.to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("index.js"), "explicit search root should still be searched");
+}
+
+#[test]
+fn exclude_dir_prunes_top_level_and_nested_directories() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("build.rs"), "").unwrap();
+    let top = tmp.path().join("build");
+    fs::create_dir_all(&top).unwrap();
+    fs::write(top.join("build.log"), "").unwrap();
+    let nested = tmp.path().join("crates").join("core").join("build");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("build.out"), "").unwrap();
+
+    let out = qro(&["-n", "--exclude-dir", "build", "build", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("build.rs"), "a file with the name is not a directory: {text}");
+    assert!(!text.contains("build.log"), "top-level directory should be pruned: {text}");
+    assert!(!text.contains("build.out"), "nested directory should be pruned: {text}");
+}
+
+// --- Glob / ignore precedence ---
+
+fn make_overlap_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("lib.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("test_lib.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "findme").unwrap();
+    tmp
+}
+
+#[test]
+fn ignore_wins_over_overlapping_glob() {
+    let tmp = make_overlap_tree();
+
+    let out = qro(&["-c", "-g", "*.rs", "-x", "test_*.rs", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("/lib.rs"), "glob should include lib.rs");
+    assert!(!text.contains("test_lib.rs"), "exclusion should beat the include");
+    assert!(!text.contains("notes.txt"), "glob should exclude non-.rs files");
+}
+
+#[test]
+fn ignore_wins_over_glob_regardless_of_flag_order() {
+    let tmp = make_overlap_tree();
+
+    let out = qro(&["-c", "-x", "test_*.rs", "-g", "*.rs", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("/lib.rs"), "glob should include lib.rs");
+    assert!(!text.contains("test_lib.rs"), "exclusion should beat the include");
+}
+
+// --- Headings and blank lines (--heading, --no-heading, --compact) ---
+
+#[test]
+fn heading_golden_output() {
+    let out = qro(&["--no-log", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n  2:    println!(\"hello\");\n\n\
+         tests/fixtures/hello.txt\n  (name match)\n  1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn compact_golden_output() {
+    let out = qro(&["--no-log", "--compact", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n  2:    println!(\"hello\");\n\
+         tests/fixtures/hello.txt\n  (name match)\n  1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn group_separator_golden_output() {
+    let out = qro(&["--no-log", "--group-separator=--", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n  2:    println!(\"hello\");\n--\n\
+         tests/fixtures/hello.txt\n  (name match)\n  1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn no_group_separator_golden_output() {
+    let out = qro(&[
+        "--no-log", "--group-separator=--", "--no-group-separator", "-i", "hello",
+        "tests/fixtures/",
+    ]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n  2:    println!(\"hello\");\n\
+         tests/fixtures/hello.txt\n  (name match)\n  1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn indent_golden_output() {
+    let out = qro(&["--no-log", "--indent", "4", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n    2:    println!(\"hello\");\n\n\
+         tests/fixtures/hello.txt\n    (name match)\n    1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn no_line_number_golden_output() {
+    let out = qro(&["--no-log", "-N", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs\n      println!(\"hello\");\n\n\
+         tests/fixtures/hello.txt\n  (name match)\n  Hello, world!\n"
+    );
+
+    let out = qro(&["--no-log", "-N", "--no-heading", "-c", "-i", "hello", "tests/fixtures/"]);
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs:    println!(\"hello\");\n\
+         tests/fixtures/hello.txt:Hello, world!\n"
+    );
+}
+
+#[test]
+fn no_heading_golden_output() {
+    let out = qro(&["--no-log", "--no-heading", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs:2:    println!(\"hello\");\n\
+         tests/fixtures/hello.txt:(name match)\n\
+         tests/fixtures/hello.txt:1:Hello, world!\n"
+    );
+}
+
+#[test]
+fn heading_overrides_earlier_no_heading() {
+    let out = qro(&["--no-log", "--no-heading", "--heading", "-c", "world", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/hello.txt\n  1:Hello, world!\n  2:Goodbye, world!\n"
+    );
+}
+
+// --- Match ordering within a file ---
+
+#[test]
+fn matches_within_file_are_in_ascending_line_order() {
+    let tmp = tempfile::tempdir().unwrap();
+    let content: String = (1..=30)
+        .map(|n| if n % 3 == 0 { format!("findme {n}\n") } else { format!("skip {n}\n") })
+        .collect();
+    fs::write(tmp.path().join("many.txt"), content).unwrap();
+
+    let out = qro(&["-c", "--no-log", "--no-heading", "findme", tmp.path().to_str().unwrap()]);
+    let line_numbers: Vec<u64> = stdout(&out)
+        .lines()
+        .map(|l| {
+            let rest = l.split_once("many.txt:").unwrap().1;
+            rest.split(':').next().unwrap().parse().unwrap()
+        })
+        .collect();
+
+    assert_eq!(line_numbers, vec![3, 6, 9, 12, 15, 18, 21, 24, 27, 30]);
+}
+
+// --- qro global ignore file ---
+
+/// Run qro with XDG_CONFIG_HOME pointing at `config_home`.
+fn qro_with_config_home(config_home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config_home)
+        .output()
+        .expect("failed to run qro")
+}
+
+fn make_global_ignore_tree() -> (tempfile::TempDir, tempfile::TempDir) {
+    let config = tempfile::tempdir().unwrap();
+    fs::create_dir_all(config.path().join("qro")).unwrap();
+    fs::write(config.path().join("qro").join("ignore"), "*.log\n").unwrap();
+
+    let tree = tempfile::tempdir().unwrap();
+    fs::write(tree.path().join("keep.txt"), "findme").unwrap();
+    fs::write(tree.path().join("skip.log"), "findme").unwrap();
+    (config, tree)
+}
+
+#[test]
+fn global_qro_ignore_excludes_files() {
+    let (config, tree) = make_global_ignore_tree();
+
+    let out = qro_with_config_home(config.path(), &["-c", "findme", tree.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("keep.txt"), "should find match in non-ignored file");
+    assert!(!text.contains("skip.log"), "global ignore should exclude .log files");
+}
+
+#[test]
+fn no_ignore_global_qro_bypasses_global_ignore() {
+    let (config, tree) = make_global_ignore_tree();
+
+    let out = qro_with_config_home(
+        config.path(),
+        &["-c", "--no-ignore-global-qro", "findme", tree.path().to_str().unwrap()],
+    );
+    let text = stdout(&out);
+
+    assert!(text.contains("skip.log"), "--no-ignore-global-qro should bypass global ignore");
+}
+
+#[test]
+fn no_ignore_bypasses_global_ignore() {
+    let (config, tree) = make_global_ignore_tree();
+
+    let out = qro_with_config_home(
+        config.path(),
+        &["-c", "--no-ignore", "findme", tree.path().to_str().unwrap()],
+    );
+    let text = stdout(&out);
+
+    assert!(text.contains("skip.log"), "--no-ignore should bypass global ignore");
+}
+
+// --- Summary output (--summary) ---
+
+#[test]
+fn summary_golden_output() {
+    let out = qro(&["--no-log", "--summary", "-i", "hello", "tests/fixtures/"]);
+
+    assert_eq!(
+        stdout(&out),
+        "tests/fixtures/greeting.rs (1 content)\n\
+         tests/fixtures/hello.txt (1 content, name match)\n"
+    );
+}
+
+#[test]
+fn summary_counts_multiple_content_lines() {
+    let out = qro(&["--no-log", "--summary", "-c", "world", "tests/fixtures/"]);
+
+    assert_eq!(stdout(&out), "tests/fixtures/hello.txt (2 content)\n");
+}
+
+#[test]
+fn summary_includes_git_log_commit_count() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Fix issue99010 in auth", "some content");
+
+    let out = qro(&["--log-only", "--summary", "issue99010", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("repo-a (git log, 1 commit)"), "got: {text}");
+}
+
+// --- Glob behaves the same in every mode ---
+
+/// Paths (heading lines) printed by qro, in output order.
+fn printed_paths(out: &Output) -> Vec<String> {
+    stdout(out)
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with(' '))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn glob_selects_same_files_in_every_mode() {
+    // Every file matches "findme" both by name and by content, so each mode
+    // should report exactly the files the glob lets through.
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("findme.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("findme.txt"), "findme").unwrap();
+    let sub = tmp.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("findme.rs"), "findme").unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    for glob in ["*.rs", "sub/*", "sub/**", "**/sub/*", "sub", "!*.txt"] {
+        let names = printed_paths(&qro(&["--no-log", "-n", "-g", glob, "findme", root]));
+        let content = printed_paths(&qro(&["--no-log", "-c", "-g", glob, "findme", root]));
+        let both = printed_paths(&qro(&["--no-log", "-g", glob, "findme", root]));
+
+        assert_eq!(names, content, "-n and -c differ for -g {glob}");
+        assert_eq!(names, both, "-n and default mode differ for -g {glob}");
+    }
+}
+
+#[test]
+fn glob_on_bare_directory_name_selects_nothing() {
+    // A glob matching only a directory doesn't select the files inside it;
+    // use `dir/**` for that. Locked in so it stays the same in every mode.
+    let tmp = tempfile::tempdir().unwrap();
+    let sub = tmp.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("findme.rs"), "findme").unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let bare = qro(&["--no-log", "-g", "sub", "findme", root]);
+    assert!(stdout(&bare).is_empty(), "got: {}", stdout(&bare));
+
+    let recursive = qro(&["--no-log", "-g", "sub/**", "findme", root]);
+    assert!(stdout(&recursive).contains("findme.rs"));
+}
+
+// --- Whole-file matching (--slurp) ---
+
+#[test]
+fn slurp_matches_across_lines() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("data.json"), "{\n  \"key\": [\n    1\n  ]\n}\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--slurp", r#""key": \[\s*1"#, tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("2:  \"key\": ["), "should report first spanned line, got: {text}");
+    assert!(text.contains("3:    1"), "should report last spanned line, got: {text}");
+    assert!(!text.contains("1:{"), "should not report lines outside the match, got: {text}");
+}
+
+#[test]
+fn slurp_reports_each_line_once() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "skip\nfindme findme\nskip\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--no-heading", "--slurp", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert_eq!(text.lines().count(), 1, "got: {text}");
+    assert!(text.ends_with("a.txt:2:findme findme\n"), "got: {text}");
+}
+
+#[test]
+fn slurp_anchors_match_at_line_boundaries() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "xfindme\nfindme\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--slurp", "^findme$", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("2:findme"), "got: {text}");
+    assert!(!text.contains("1:xfindme"), "got: {text}");
+}
+
+// --- Environment expansion (--expand-env) ---
+
+#[test]
+fn expand_env_in_path_and_globs() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("keep.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("skip_test.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "findme").unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(["-c", "--no-log", "--expand-env", "-g", "*.$QRO_EXT", "-x", "${QRO_SKIP}*", "findme", "$QRO_ROOT"])
+        .env("QRO_ROOT", tmp.path())
+        .env("QRO_EXT", "rs")
+        .env("QRO_SKIP", "skip_")
+        .output()
+        .expect("failed to run qro");
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("keep.rs"), "should search expanded path with expanded glob");
+    assert!(!text.contains("skip_test.rs"), "expanded -x should exclude");
+    assert!(!text.contains("notes.txt"), "expanded -g should filter");
+}
+
+#[test]
+fn env_not_expanded_without_flag() {
+    let out = Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(["-c", "--no-log", "hello", "$QRO_ROOT"])
+        .env("QRO_ROOT", "tests/fixtures")
+        .output()
+        .expect("failed to run qro");
+
+    assert!(stdout(&out).is_empty(), "path should be taken literally");
+}
+
+// --- Sorting (--sort) ---
+
+#[test]
+fn sort_depth_puts_shallow_paths_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    let deep = tmp.path().join("a").join("b");
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(deep.join("findme3.txt"), "").unwrap();
+    fs::write(tmp.path().join("a").join("findme2.txt"), "").unwrap();
+    fs::write(tmp.path().join("z_findme1.txt"), "").unwrap();
+    fs::write(tmp.path().join("findme1.txt"), "").unwrap();
+
+    let out = qro(&["-n", "--no-log", "--sort", "depth", "findme", tmp.path().to_str().unwrap()]);
+    let names: Vec<String> = printed_paths(&out)
+        .iter()
+        .map(|p| p.strip_prefix(tmp.path().to_str().unwrap()).unwrap().to_string())
+        .collect();
+
+    assert_eq!(
+        names,
+        vec!["/findme1.txt", "/z_findme1.txt", "/a/findme2.txt", "/a/b/findme3.txt"]
+    );
+}
+
+#[test]
+fn sort_path_is_default() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("a")).unwrap();
+    fs::write(tmp.path().join("a").join("findme.txt"), "").unwrap();
+    fs::write(tmp.path().join("b_findme.txt"), "").unwrap();
+
+    let out = qro(&["-n", "--no-log", "findme", tmp.path().to_str().unwrap()]);
+    let paths = printed_paths(&out);
+
+    assert!(paths[0].ends_with("/a/findme.txt"), "got: {paths:?}");
+    assert!(paths[1].ends_with("/b_findme.txt"), "got: {paths:?}");
+}
+
+// --- Invalid search path ---
+
+#[test]
+fn nonexistent_path_errors_upfront() {
+    let out = qro(&["hello", "tests/does/not/exist"]);
+
+    assert!(!out.status.success(), "should exit nonzero");
+    assert_eq!(
+        stderr(&out),
+        "qro: tests/does/not/exist: No such file or directory\n"
+    );
+    assert!(stdout(&out).is_empty());
+}
+
+#[test]
+fn nonexistent_path_errors_in_log_only_mode() {
+    let out = qro(&["--log-only", "hello", "tests/does/not/exist"]);
+
+    assert!(!out.status.success(), "should exit nonzero");
+    assert!(stderr(&out).contains("tests/does/not/exist: No such file or directory"));
+}
+
+// --- Restricting log search to some repos (--log-repo-glob) ---
+
+#[test]
+fn log_repo_glob_selects_matching_repos() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "app-frontend", "Fix issue99011 in ui", "issue99011");
+    make_git_repo(tmp.path(), "app-backend", "Fix issue99011 in api", "issue99011");
+
+    let out = qro(&["--log-repo-glob", "*frontend*", "issue99011", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success());
+    assert!(text.contains("app-frontend (git log):"), "got: {text}");
+    assert!(!text.contains("app-backend (git log):"), "got: {text}");
+    // File results still come from every repo.
+    assert!(text.contains("app-backend/file.txt"), "got: {text}");
+}
+
+#[test]
+fn log_repo_glob_invalid_pattern_errors() {
+    let out = qro(&["--log-only", "--log-repo-glob", "[", "test", "."]);
+
+    assert!(!out.status.success(), "invalid glob should fail");
+}
+
+// --- Searching commit diffs too (--log-everything) ---
+
+#[test]
+fn log_everything_reports_message_only_hit() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Fix issue99012 in auth", "unrelated");
+
+    let out = qro(&["--log-only", "--log-everything", "issue99012", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("Fix issue99012 in auth\n"), "got: {text}");
+    assert!(!text.contains("diff match"), "got: {text}");
+}
+
+#[test]
+fn log_everything_reports_diff_only_hit() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Initial commit", "issue99013 in the file");
+
+    let out = qro(&["--log-only", "--log-everything", "issue99013", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("Initial commit (diff match)"), "got: {text}");
+}
+
+#[test]
+fn log_everything_merges_message_and_diff_hit() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Add issue99014", "issue99014 in the file");
+
+    let out = qro(&["--log-only", "--log-everything", "issue99014", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("Add issue99014 (message and diff match)"), "got: {text}");
+    assert_eq!(text.matches("Add issue99014").count(), 1, "should dedupe by hash: {text}");
+}
+
+#[test]
+fn diff_hits_need_log_everything() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Initial commit", "issue99015 in the file");
+
+    let out = qro(&["--log-only", "issue99015", tmp.path().to_str().unwrap()]);
+
+    assert!(stdout(&out).is_empty(), "got: {}", stdout(&out));
+}
+
+// --- Project config (.qroconfig) ---
+
+fn make_config_project(config: &str) -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join(".qroconfig"), config).unwrap();
+    fs::write(tmp.path().join("main.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "findme").unwrap();
+    tmp
+}
+
+#[test]
+fn qroconfig_sets_default_type() {
+    let tmp = make_config_project("# rust only\n--type=rust\n\n--no-log\n");
+
+    let out = qro(&["-c", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("main.rs"), "got: {text}");
+    assert!(!text.contains("notes.txt"), "config --type should apply: {text}");
+}
+
+#[test]
+fn qroconfig_in_ancestor_applies_to_subdirectory() {
+    let tmp = make_config_project("--type=rust\n");
+    let sub = tmp.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("lib.rs"), "findme").unwrap();
+    fs::write(sub.join("readme.txt"), "findme").unwrap();
+
+    let out = qro(&["-c", "--no-log", "findme", sub.to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("lib.rs"), "got: {text}");
+    assert!(!text.contains("readme.txt"), "ancestor config should apply: {text}");
+}
+
+#[test]
+fn command_line_overrides_qroconfig() {
+    let tmp = make_config_project("--type=rust\n");
+
+    let out = qro(&["-c", "--no-log", "-t", "txt", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("notes.txt"), "CLI --type should win: {text}");
+    assert!(!text.contains("main.rs"), "CLI --type should win: {text}");
+}
+
+#[test]
+fn no_config_ignores_qroconfig() {
+    let tmp = make_config_project("--type=rust\n");
+
+    let out = qro(&["-c", "--no-log", "--no-config", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("main.rs"), "got: {text}");
+    assert!(text.contains("notes.txt"), "--no-config should skip config: {text}");
+}
+
+#[test]
+fn qroconfig_rejects_flags_that_touch_other_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let victim = tmp.path().join("victim.txt");
+    fs::write(&victim, "keep me\n").unwrap();
+    let project = make_config_project(&format!("--print-files-to={}\n", victim.display()));
+
+    let out = qro(&["--no-log", "findme", project.path().to_str().unwrap()]);
+
+    assert!(!out.status.success());
+    assert!(
+        stderr(&out).contains("--print-files-to can only be given on the command line"),
+        "{}",
+        stderr(&out)
+    );
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "keep me\n");
+
+    for config in ["--git-log-arg=--output=x\n", "--cached\n", "--git-dir\n/srv/x.git\n"] {
+        let project = make_config_project(config);
+        let out = qro(&["--no-log", "findme", project.path().to_str().unwrap()]);
+        assert!(!out.status.success(), "{config:?} should be rejected");
+    }
+}
+
+#[test]
+fn sort_name_clusters_same_named_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    for dir in ["a", "b", "c"] {
+        fs::create_dir_all(tmp.path().join(dir)).unwrap();
+    }
+    fs::write(tmp.path().join("a").join("mod.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("a").join("zz.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("b").join("lib.rs"), "findme").unwrap();
+    fs::write(tmp.path().join("c").join("mod.rs"), "findme").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--sort", "name", "findme", tmp.path().to_str().unwrap()]);
+    let names: Vec<String> = printed_paths(&out)
+        .iter()
+        .map(|p| p.strip_prefix(tmp.path().to_str().unwrap()).unwrap().to_string())
+        .collect();
+
+    assert_eq!(names, vec!["/b/lib.rs", "/a/mod.rs", "/c/mod.rs", "/a/zz.rs"]);
+}
+
+// --- Reverse match order (--reverse) ---
+
+#[test]
+fn reverse_lists_matches_last_line_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("app.log"),
+        "ERROR one\nok\nERROR two\nok\nERROR three\n",
+    )
+    .unwrap();
+
+    let out = qro(&["-c", "--no-log", "--reverse", "ERROR", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    let lines: Vec<&str> = text.lines().skip(1).map(str::trim).collect();
+    assert_eq!(lines, vec!["5:ERROR three", "3:ERROR two", "1:ERROR one"]);
+}
+
+// --- Matching canonical paths (--canonicalize) ---
+
+/// Run qro from `dir` as the working directory.
+fn qro_in(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run qro")
+}
+
+#[test]
+fn canonicalize_matches_directories_above_search_root() {
+    let tmp = tempfile::tempdir().unwrap();
+    let project = tmp.path().join("projectx99016");
+    fs::create_dir(&project).unwrap();
+    fs::write(project.join("file.txt"), "").unwrap();
+
+    let plain = qro_in(&project, &["-n", "--no-log", "projectx99016", "."]);
+    assert!(stdout(&plain).is_empty(), "relative path shouldn't match: {}", stdout(&plain));
+
+    let canonical = qro_in(&project, &["-n", "--no-log", "--canonicalize", "projectx99016", "."]);
+    let text = stdout(&canonical);
+    assert_eq!(text, "./file.txt\n", "should match via canonical path but print walked path");
+}
+
+// --- Verbose version (--version-verbose) ---
+
+#[test]
+fn version_verbose_reports_build_details() {
+    let out = qro(&["--version-verbose"]);
+    let text = stdout(&out);
+
+    assert!(out.status.success());
+    assert!(
+        text.starts_with(&format!("qro {}\n", env!("CARGO_PKG_VERSION"))),
+        "got: {text}"
+    );
+    assert!(text.contains("regex engine:"), "got: {text}");
+    assert!(text.contains("PCRE2: not available"), "got: {text}");
+    assert!(text.contains("git: git version"), "got: {text}");
+}
+
+// --- Shebang type detection (--type-shebang) ---
+
+fn make_script_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("build.sh"), "echo findme\n").unwrap();
+    fs::write(tmp.path().join("deploy"), "#!/bin/bash\necho findme\n").unwrap();
+    fs::write(tmp.path().join("tool"), "#!/usr/bin/env python3\nprint('findme')\n").unwrap();
+    fs::write(tmp.path().join("NOTES"), "findme\n").unwrap();
+    tmp
+}
+
+#[test]
+fn type_filter_skips_extensionless_scripts_by_default() {
+    let tmp = make_script_tree();
+
+    let out = qro(&["-c", "--no-log", "-t", "sh", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("build.sh"), "got: {text}");
+    assert!(!text.contains("deploy"), "got: {text}");
+}
+
+#[test]
+fn type_shebang_includes_extensionless_scripts() {
+    let tmp = make_script_tree();
+
+    let out = qro(&["-c", "--no-log", "-t", "sh", "--type-shebang", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("build.sh"), "got: {text}");
+    assert!(text.contains("deploy"), "bash shebang should count as sh: {text}");
+    assert!(!text.contains("tool"), "python script isn't sh: {text}");
+    assert!(!text.contains("NOTES"), "no shebang means no type: {text}");
+}
+
+#[test]
+fn type_shebang_requires_type() {
+    let out = qro(&["--type-shebang", "findme", "tests/fixtures/"]);
+
+    assert!(!out.status.success(), "--type-shebang without -t should fail");
+}
+
+#[test]
+fn type_shebang_composes_with_stop_at() {
+    let tmp = make_script_tree();
+    let vendored = tmp.path().join("vendor");
+    fs::create_dir(&vendored).unwrap();
+    fs::write(vendored.join("install"), "#!/bin/sh\necho findme\n").unwrap();
+
+    let out = qro(&[
+        "-c", "--no-log", "-t", "sh", "--type-shebang", "--stop-at", "vendor", "findme",
+        tmp.path().to_str().unwrap(),
+    ]);
+    let text = stdout(&out);
+
+    assert!(text.contains("deploy"), "got: {text}");
+    assert!(!text.contains("install"), "--stop-at should still prune: {text}");
+}
+
+// --- Hidden files only (--hidden-only) ---
+
+#[test]
+fn hidden_only_searches_only_dotfiles() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join(".bashrc"), "findme").unwrap();
+    fs::write(tmp.path().join("visible.txt"), "findme").unwrap();
+    let config = tmp.path().join(".config");
+    fs::create_dir(&config).unwrap();
+    fs::write(config.join("app.toml"), "findme").unwrap();
+    let src = tmp.path().join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join(".env"), "findme").unwrap();
+    fs::write(src.join("main.rs"), "findme").unwrap();
+
+    for mode in ["-c", "-n", "--summary"] {
+        let out = qro(&[mode, "--no-log", "--hidden-only", "", tmp.path().to_str().unwrap()]);
+        let text = stdout(&out);
+
+        assert!(text.contains(".bashrc"), "{mode}: got: {text}");
+        assert!(text.contains(".config/app.toml"), "{mode}: files in hidden dirs count: {text}");
+        assert!(text.contains("src/.env"), "{mode}: nested dotfiles count: {text}");
+        assert!(!text.contains("visible.txt"), "{mode}: got: {text}");
+        assert!(!text.contains("main.rs"), "{mode}: got: {text}");
+    }
+}
+
+// --- Literal file paths ---
+
+#[test]
+fn named_file_is_searched_even_when_gitignored() {
+    let tmp = tempfile::tempdir().unwrap();
+    Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(tmp.path())
+        .output()
+        .unwrap();
+    fs::write(tmp.path().join(".gitignore"), "secret.log\n").unwrap();
+    let file = tmp.path().join("secret.log");
+    fs::write(&file, "findme here\n").unwrap();
+
+    // Walking the directory honours .gitignore...
+    let out = qro(&["-c", "--no-log", "findme", tmp.path().to_str().unwrap()]);
+    assert!(!stdout(&out).contains("secret.log"), "got: {}", stdout(&out));
+
+    // ...but a file named on the command line is always searched.
+    for mode in ["-c", "-n"] {
+        let out = qro(&[mode, "--no-log", "secret|findme", file.to_str().unwrap()]);
+        let text = stdout(&out);
+        assert!(out.status.success(), "{mode}: stderr: {}", stderr(&out));
+        assert!(text.contains("secret.log"), "{mode}: got: {text}");
+    }
+}
+
+#[test]
+fn named_file_ignores_type_and_glob_filters() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join("notes.txt");
+    fs::write(&file, "findme\n").unwrap();
+
+    let out = qro(&[
+        "-c", "--no-log", "-t", "rust", "-g", "*.rs", "findme",
+        file.to_str().unwrap(),
+    ]);
+    let text = stdout(&out);
+
+    assert!(text.contains("1:findme"), "got: {text}");
+}
+
+// --- Per-pass case rules ---
+
+fn make_mixed_case_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("README.md"), "see the readme section\n").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "Readme links\n").unwrap();
+    tmp
+}
+
+#[test]
+fn name_ignore_case_leaves_content_case_sensitive() {
+    let tmp = make_mixed_case_tree();
+
+    let out = qro(&["--no-log", "--name-ignore-case", "readme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("README.md"), "name pass should ignore case: {text}");
+    assert!(text.contains("1:see the readme section"), "got: {text}");
+    assert!(!text.contains("notes.txt"), "content pass should stay case-sensitive: {text}");
+}
+
+#[test]
+fn content_ignore_case_false_overrides_global_flag() {
+    let tmp = make_mixed_case_tree();
+
+    let out = qro(&[
+        "--no-log", "-i", "--content-ignore-case=false", "readme",
+        tmp.path().to_str().unwrap(),
+    ]);
+    let text = stdout(&out);
+
+    assert!(text.contains("README.md"), "-i still applies to names: {text}");
+    assert!(!text.contains("notes.txt"), "content override should win over -i: {text}");
+}
+
+#[test]
+fn exclusions_follow_each_pass_case_rule() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("alpha.txt"), "alpha LINKS\n").unwrap();
+    fs::write(tmp.path().join("beta.txt"), "alpha notes\n").unwrap();
+    let dir = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "--content-ignore-case", "-V", "links", "alpha", dir]);
+    let text = stdout(&out);
+    assert!(!text.contains("alpha LINKS"), "-V should ignore case in content: {text}");
+    assert!(text.contains("alpha notes"), "got: {text}");
+
+    let out = qro(&["--no-log", "--names-only", "--name-ignore-case", "-V", "TXT", "alpha", dir]);
+    let text = stdout(&out);
+    assert!(!text.contains("alpha.txt"), "-V should ignore case in names: {text}");
+}
+
+// --- Quiet mode (-q) ---
+
+#[test]
+fn quiet_log_only_exit_codes() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo-a", "Fix issue44301 in auth", "some content");
+    make_git_repo(tmp.path(), "repo-b", "Mention issue44301 again", "other content");
+
+    let out = qro(&["-q", "--log-only", "issue44301", tmp.path().to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0), "stderr: {}", stderr(&out));
+    assert_eq!(stdout(&out), "", "--quiet should print nothing");
+
+    let out = qro(&["-q", "--log-only", "issue44399", tmp.path().to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stdout(&out), "");
+    assert_eq!(stderr(&out), "", "no match is not an error");
+}
+
+#[test]
+fn quiet_file_search_exit_codes() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "findme\n").unwrap();
+
+    let out = qro(&["-q", "--no-log", "findme", tmp.path().to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(stdout(&out), "");
+
+    let out = qro(&["-q", "--no-log", "nothing44301", tmp.path().to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+}
+
+// --- Block ordering tie-breaks ---
+
+#[test]
+fn git_log_block_sorts_after_every_file_in_its_repo() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "proj", "findme commit", "findme\n");
+    fs::write(tmp.path().join("proj.txt"), "findme\n").unwrap();
+    fs::write(repo.join("\u{e9}t\u{e9}.txt"), "findme\n").unwrap();
+    fs::write(repo.join("\x7f"), "findme\n").unwrap();
+    // Same key as the repo's git log block.
+    fs::write(repo.join(char::MAX.to_string()), "findme\n").unwrap();
+
+    let out = qro(&["--summary", "-c", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+    let root = tmp.path().to_str().unwrap();
+    let expected = [
+        format!("{root}/proj.txt (1 content)"),
+        format!("{root}/proj/file.txt (1 content)"),
+        format!("{root}/proj/\x7f (1 content)"),
+        format!("{root}/proj/\u{e9}t\u{e9}.txt (1 content)"),
+        format!("{root}/proj/{} (1 content)", char::MAX),
+        format!("{root}/proj (git log, 1 commit)"),
+    ];
+
+    assert_eq!(text.lines().collect::<Vec<_>>(), expected, "got: {text}");
+}
+
+// --- ASCII word boundaries (--no-unicode-boundaries) ---
+
+#[test]
+fn no_unicode_boundaries_treats_non_ascii_letters_as_boundaries() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("menu.txt"), "caf\u{e9} au lait\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "-w", "caf", tmp.path().to_str().unwrap()]);
+    assert_eq!(stdout(&out), "", "Unicode \\b sees é as a word character");
+
+    let out = qro(&[
+        "-c", "--no-log", "-w", "--no-unicode-boundaries", "caf",
+        tmp.path().to_str().unwrap(),
+    ]);
+    assert!(stdout(&out).contains("1:caf\u{e9} au lait"), "got: {}", stdout(&out));
+}
+
+// --- Statistics (--stats) ---
+
+#[test]
+fn stats_reports_each_phase() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo", "Add findme", "findme\n");
+
+    let out = qro(&["--stats", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("\nstats:\n"), "got: {text}");
+    assert!(text.contains("  content lines: 1\n"), "got: {text}");
+    assert!(text.contains("  commits: 1\n"), "got: {text}");
+    for label in ["name search: ", "content search: ", "git log: ", "total: "] {
+        assert!(text.contains(label), "missing {label:?}: {text}");
+    }
+
+    // Phases that don't run aren't listed.
+    let out = qro(&["--stats", "-c", "--no-log", "findme", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+    assert!(text.contains("content search: "), "got: {text}");
+    assert!(!text.contains("name search: "), "got: {text}");
+    assert!(!text.contains("git log: "), "got: {text}");
+}
+
+// --- File encodings (--encoding) ---
+
+fn make_mixed_encoding_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("utf8.txt"), "caf\u{e9} utf8\n").unwrap();
+    fs::write(tmp.path().join("latin1.txt"), b"caf\xe9 latin1\n").unwrap();
+    let mut utf16 = vec![0xFF, 0xFE];
+    for unit in "caf\u{e9} utf16\n".encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    fs::write(tmp.path().join("utf16.txt"), utf16).unwrap();
+    tmp
+}
+
+#[test]
+fn encoding_auto_searches_mixed_encodings() {
+    let tmp = make_mixed_encoding_tree();
+
+    let out = qro(&["-c", "--no-log", "--encoding", "auto", "caf\u{e9}", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("1:caf\u{e9} utf8"), "got: {text}");
+    assert!(text.contains("1:caf\u{e9} latin1"), "got: {text}");
+    assert!(text.contains("1:caf\u{e9} utf16"), "got: {text}");
+}
+
+#[test]
+fn encoding_defaults_to_utf8() {
+    let tmp = make_mixed_encoding_tree();
+
+    let out = qro(&["-c", "--no-log", "caf\u{e9}", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("utf8.txt"), "got: {text}");
+    assert!(text.contains("utf16.txt"), "a BOM is always honoured: {text}");
+    assert!(!text.contains("latin1.txt"), "got: {text}");
+}
+
+#[test]
+fn encoding_rejects_unknown_label() {
+    let tmp = tempfile::tempdir().unwrap();
+
+    let out = qro(&["-c", "--encoding", "klingon", "x", tmp.path().to_str().unwrap()]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("klingon"), "got: {}", stderr(&out));
+}
+
+// --- Matched file list (--print-files-to) ---
+
+#[test]
+fn print_files_to_writes_nul_separated_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo", "findme commit", "findme\n");
+    fs::write(tmp.path().join("a.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("other.txt"), "nothing\n").unwrap();
+    let list = tempfile::NamedTempFile::new().unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--print-files-to", list.path().to_str().unwrap(), "findme", root]);
+    let text = stdout(&out);
+
+    assert!(text.contains("1:findme"), "normal output still goes to stdout: {text}");
+    assert!(text.contains("(git log):"), "got: {text}");
+    let written = fs::read_to_string(list.path()).unwrap();
+    assert_eq!(written, format!("{root}/a.txt\0{root}/repo/file.txt\0"));
+}
+
+// --- Byte sequences (--hex) ---
+
+#[test]
+fn hex_reports_byte_offsets_in_binary_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut blob = vec![0u8; 16];
+    blob.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    blob.extend_from_slice(&[0x00, 0x01]);
+    blob.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    fs::write(tmp.path().join("firmware.bin"), &blob).unwrap();
+    fs::write(tmp.path().join("deadbeef.txt"), "no bytes here").unwrap();
+
+    let out = qro(&["--hex", "de ad BE EF", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("firmware.bin"), "got: {text}");
+    assert!(text.contains("  byte 16 (0x10)\n"), "got: {text}");
+    assert!(text.contains("  byte 22 (0x16)\n"), "got: {text}");
+    assert!(!text.contains("deadbeef.txt"), "--hex doesn't match file names: {text}");
+}
+
+#[test]
+fn hex_rejects_malformed_pattern() {
+    let tmp = tempfile::tempdir().unwrap();
+
+    let out = qro(&["--hex", "dea", tmp.path().to_str().unwrap()]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("odd number of hex digits"), "got: {}", stderr(&out));
+}
+
+// --- Extended attributes (--xattr) ---
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn xattr_filters_name_matches() {
+    use rustix::fs::{setxattr, XattrFlags};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let tagged = tmp.path().join("report-tagged.txt");
+    let other = tmp.path().join("report-other.txt");
+    let plain = tmp.path().join("report-plain.txt");
+    for path in [&tagged, &other, &plain] {
+        fs::write(path, "").unwrap();
+    }
+    if setxattr(&tagged, "user.qro.review", b"done", XattrFlags::empty()).is_err() {
+        eprintln!("skipping: filesystem doesn't support user xattrs");
+        return;
+    }
+    setxattr(&other, "user.qro.review", b"todo", XattrFlags::empty()).unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let out = qro(&["-n", "--xattr", "user.qro.review", "report", root]);
+    let text = stdout(&out);
+    assert!(text.contains("report-tagged.txt"), "got: {text}");
+    assert!(text.contains("report-other.txt"), "got: {text}");
+    assert!(!text.contains("report-plain.txt"), "got: {text}");
+
+    let out = qro(&["-n", "--xattr", "user.qro.review=done", "report", root]);
+    let text = stdout(&out);
+    assert!(text.contains("report-tagged.txt"), "got: {text}");
+    assert!(!text.contains("report-other.txt"), "value must match: {text}");
+}
+
+// --- Total matching lines (--count-lines-total) ---
+
+#[test]
+fn count_lines_total_prints_a_single_number() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo", "findme in a commit", "findme\nnothing\nfindme again\n");
+    fs::write(tmp.path().join("a.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("findme.txt"), "no match inside\n").unwrap();
+    fs::write(tmp.path().join("b.txt"), "findme findme\nfindme\n").unwrap();
+
+    let out = qro(&["--count-lines-total", "findme", tmp.path().to_str().unwrap()]);
+
+    // 2 in repo/file.txt, 1 in a.txt, 2 in b.txt; names and commits don't count.
+    assert_eq!(stdout(&out), "5\n");
+}
+
+// --- Directory name matches (--include-dirs) ---
+
+#[test]
+fn include_dirs_matches_directory_names() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("widgets");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("button.rs"), "").unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let out = qro(&["-n", "widgets$", root]);
+    assert_eq!(stdout(&out), "", "directories don't match by default");
+
+    let out = qro(&["-n", "--include-dirs", "widgets$", root]);
+    assert_eq!(stdout(&out), format!("{root}/widgets/\n"));
+
+    // The search root is a directory like any other.
+    let out = qro(&["-n", "--include-dirs", "widgets/?$", dir.to_str().unwrap()]);
+    assert_eq!(stdout(&out), format!("{root}/widgets/\n"));
+}
+
+#[test]
+fn unknown_type_suggests_closest_name() {
+    let out = qro(&["-c", "-t", "pyhton", "hello", "tests/fixtures/"]);
+
+    assert!(!out.status.success());
+    assert!(
+        stderr(&out).contains("unknown file type `pyhton`; did you mean `python`?"),
+        "got: {}",
+        stderr(&out)
+    );
+}
+
+#[test]
+fn unknown_type_without_close_name() {
+    let out = qro(&["-c", "-t", "zzzzzz", "hello", "tests/fixtures/"]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("unknown file type `zzzzzz`\n"), "got: {}", stderr(&out));
+}
+
+// --- File name pre-filter (--name) ---
+
+#[test]
+fn name_prefilter_limits_content_search() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    let tests_dir = src.join("parser_test");
+    fs::create_dir_all(&tests_dir).unwrap();
+    fs::write(src.join("lexer_test.rs"), "assert_eq!(1, 1);\n").unwrap();
+    fs::write(src.join("lexer.rs"), "assert_eq!(2, 2);\n").unwrap();
+    fs::write(tests_dir.join("cases.rs"), "assert_eq!(3, 3);\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--name", "_test", "assert_eq", src.to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("lexer_test.rs"), "got: {text}");
+    assert!(!text.contains("lexer.rs"), "name doesn't match: {text}");
+    assert!(!text.contains("cases.rs"), "only the file name counts, not the directory: {text}");
+}
+
+// --- Repeated lines (--dedup) ---
+
+#[test]
+fn dedup_scopes() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("a.log"),
+        "ERROR disk full\nERROR disk full\nok\nERROR timeout\nERROR disk full\n",
+    )
+    .unwrap();
+    fs::write(tmp.path().join("b.log"), "ERROR disk full\nERROR crash\n").unwrap();
+    let root = tmp.path().to_str().unwrap();
+    let run = |flag: &str| {
+        stdout(&qro(&["-c", "--no-log", "--no-heading", flag, "ERROR", root]))
+            .replace(&format!("{root}/"), "")
+    };
+
+    assert_eq!(
+        run("--dedup=consecutive"),
+        "a.log:1:ERROR disk full\na.log:4:ERROR timeout\na.log:5:ERROR disk full\n\
+         b.log:1:ERROR disk full\nb.log:2:ERROR crash\n"
+    );
+    assert_eq!(
+        run("--dedup"),
+        "a.log:1:ERROR disk full\na.log:4:ERROR timeout\n\
+         b.log:1:ERROR disk full\nb.log:2:ERROR crash\n"
+    );
+    assert_eq!(
+        run("--dedup=all"),
+        "a.log:1:ERROR disk full\na.log:4:ERROR timeout\nb.log:2:ERROR crash\n"
+    );
+}
+
+// --- Matching inside comments or strings (--in) ---
+
+fn make_comment_tree() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("lib.rs"),
+        "fn retry() {}\n// retry on failure\nlet msg = \"retry later\";\n/*\n * retry loop\n */\n",
+    )
+    .unwrap();
+    fs::write(tmp.path().join("tool.py"), "def retry():\n    pass  # retry once\n").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "retry\n").unwrap();
+    tmp
+}
+
+#[test]
+fn in_comments_skips_code_and_strings() {
+    let tmp = make_comment_tree();
+
+    let out = qro(&["--no-log", "--no-heading", "--in", "comments", "retry", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("lib.rs:2:// retry on failure"), "got: {text}");
+    assert!(text.contains("lib.rs:5: * retry loop"), "block comments count: {text}");
+    assert!(text.contains("tool.py:2:    pass  # retry once"), "got: {text}");
+    assert!(!text.contains(":1:"), "code lines shouldn't match: {text}");
+    assert!(!text.contains("retry later"), "strings aren't comments: {text}");
+    assert!(!text.contains("notes.txt"), "unsupported languages are skipped: {text}");
+}
+
+#[test]
+fn in_strings_matches_only_string_literals() {
+    let tmp = make_comment_tree();
+
+    let out = qro(&["-c", "--no-log", "--no-heading", "--in", "strings", "retry", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert_eq!(text, format!("{}/lib.rs:3:let msg = \"retry later\";\n", tmp.path().display()));
+}
+
+#[test]
+fn in_comments_empty_match_at_end_of_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.rs"), "fn main() { // hi").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--in", "comments", "$", tmp.path().to_str().unwrap()]);
+
+    assert!(out.status.success(), "{}", stderr(&out));
+}
+
+#[test]
+fn in_comments_reads_the_file_in_its_encoding() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut utf16 = vec![0xFF, 0xFE];
+    for unit in "let retry = 1;\n// retry later\n".encode_utf16() {
+        utf16.extend(unit.to_le_bytes());
+    }
+    fs::write(tmp.path().join("a.rs"), utf16).unwrap();
+    fs::write(tmp.path().join("b.rs"), b"let s = \"caf\xe9\";\n// retry\n").unwrap();
+
+    let out = qro(&["-c", "--no-log", "--encoding", "auto", "--in", "comments", "retry", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("2:// retry later"), "got: {text}");
+    assert!(!text.contains("1:let retry"), "got: {text}");
+    assert!(text.contains("2:// retry\n"), "got: {text}");
+}
+
+// --- Explicit empty result (--print-empty) ---
+
+#[test]
+fn print_empty_marks_only_zero_matches() {
+    let out = qro(&["--no-log", "--print-empty", "nothing47101", "tests/fixtures/"]);
+    assert_eq!(stdout(&out), "(no matches)\n");
+
+    let out = qro(&["--no-log", "--print-empty=NONE", "nothing47101", "tests/fixtures/"]);
+    assert_eq!(stdout(&out), "NONE\n");
+
+    let out = qro(&["--no-log", "--print-empty", "-c", "world", "tests/fixtures/"]);
+    assert!(!stdout(&out).contains("(no matches)"), "got: {}", stdout(&out));
+}
+
+// --- Benchmarking (--benchmark, hidden) ---
+
+#[test]
+fn benchmark_reports_timings_without_results() {
+    let out = qro(&["--no-log", "--benchmark", "3", "hello", "tests/fixtures/"]);
+    let err = stderr(&out);
+
+    assert!(out.status.success(), "stderr: {err}");
+    assert_eq!(stdout(&out), "", "results are discarded");
+    assert!(err.starts_with("qro: 3 runs: min "), "got: {err}");
+    assert!(err.contains(", median ") && err.contains(", max "), "got: {err}");
+}
+
+#[test]
+fn benchmark_is_hidden_from_help() {
+    let out = qro(&["--help"]);
+
+    assert!(!stdout(&out).contains("--benchmark"));
+}
+
+// --- Skipping long-line files (--max-line-length) ---
+
+#[test]
+fn max_line_length_skips_minified_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("app.min.js"), format!("findme;{}\n", "x=1;".repeat(500))).unwrap();
+    fs::write(tmp.path().join("app.js"), "findme();\n").unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let out = qro(&["-c", "--no-log", "--max-line-length", "200", "findme", root]);
+    let text = stdout(&out);
+    assert!(text.contains("app.js"), "got: {text}");
+    assert!(!text.contains("app.min.js"), "got: {text}");
+    assert_eq!(stderr(&out), "", "the note is only shown with --verbose");
+
+    let out = qro(&["-c", "--no-log", "--verbose", "--max-line-length", "200", "findme", root]);
+    assert!(stderr(&out).contains("app.min.js: skipped, has a line over 200 bytes"));
+
+    let out = qro(&["-c", "--no-log", "findme", root]);
+    assert!(stdout(&out).contains("app.min.js"), "searched without the flag");
+}
+
+#[test]
+fn max_line_length_skips_a_file_whose_long_line_follows_a_match() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("bundle.js"), format!("findme;\n{}\n", "x=1;".repeat(500))).unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    for mode in ["--no-heading", "--slurp"] {
+        let out = qro(&["-c", "--no-log", mode, "--max-line-length", "200", "findme", root]);
+
+        assert!(out.status.success(), "{mode}: {}", stderr(&out));
+        assert_eq!(stdout(&out), "", "{mode}");
+        assert_eq!(stderr(&out), "", "{mode}");
+    }
+}
+
+// --- Git log block order (--sort-repos) ---
+
+/// Add an empty commit with message `msg` to `repo`.
+fn add_commit(repo: &Path, msg: &str) {
+    git_in(repo, &["commit", "-q", "--allow-empty", "-m", msg]);
+}
+
+#[test]
+fn sort_repos_by_commit_count() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "alpha", "issue47501 one", "");
+    let beta = make_git_repo(tmp.path(), "beta", "issue47501 one", "");
+    add_commit(&beta, "issue47501 two");
+    add_commit(&beta, "issue47501 three");
+    let gamma = make_git_repo(tmp.path(), "gamma", "issue47501 one", "");
+    add_commit(&gamma, "issue47501 two");
+    fs::write(tmp.path().join("zz-issue47501.txt"), "").unwrap();
+    let root = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--summary", "--sort-repos", "commits", "issue47501", root]);
+    assert_eq!(
+        stdout(&out),
+        format!(
+            "{root}/zz-issue47501.txt (name match)\n\
+             {root}/beta (git log, 3 commits)\n\
+             {root}/gamma (git log, 2 commits)\n\
+             {root}/alpha (git log, 1 commit)\n"
+        )
+    );
+
+    // The default interleaves repos with files by path.
+    let out = qro(&["--summary", "issue47501", root]);
+    assert!(stdout(&out).starts_with(&format!("{root}/alpha (git log, 1 commit)\n")));
+}
+
+// --- Extra git log arguments (--git-log-arg) ---
+
+#[test]
+fn git_log_arg_is_forwarded() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "repo", "issue47601 on main", "");
+    git_in(&repo, &["checkout", "-q", "-b", "side"]);
+    add_commit(&repo, "issue47601 on side");
+    git_in(&repo, &["checkout", "-q", "-"]);
+    git_in(&repo, &["merge", "-q", "--no-ff", "-m", "merge side", "side"]);
+    let path = repo.to_str().unwrap();
+
+    let out = qro(&["--log-only", "issue47601", path]);
+    assert!(stdout(&out).contains("on side"), "got: {}", stdout(&out));
+
+    let out = qro(&["--log-only", "--git-log-arg", "--first-parent", "issue47601", path]);
+    let text = stdout(&out);
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("on main"), "got: {text}");
+    assert!(!text.contains("on side"), "--first-parent should skip the side branch: {text}");
+}
+
+#[test]
+fn git_log_arg_rejects_options_qro_sets() {
+    for arg in ["--format=%H", "--grep=x", "-Gfoo", "--pretty"] {
+        let out = qro(&["--log-only", &format!("--git-log-arg={arg}"), "x", "."]);
+
+        assert!(!out.status.success(), "{arg} should be rejected");
+        assert!(stderr(&out).contains("qro sets this git log option itself"), "got: {}", stderr(&out));
+    }
+}
+
+#[test]
+fn git_log_arg_rejects_options_that_change_the_output() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("log.txt");
+    let output = format!("--output={}", target.display());
+    for arg in [
+        output.as_str(), "--name-only", "--name-status", "--stat", "-p", "--patch", "--graph", "-z",
+    ] {
+        let out = qro(&["--log-only", &format!("--git-log-arg={arg}"), "x", "."]);
+
+        assert!(!out.status.success(), "{arg} should be rejected");
+        assert!(stderr(&out).contains("changes the output qro reads"), "got: {}", stderr(&out));
+    }
+    assert!(!target.exists(), "git shouldn't have run with --output");
+}
+
+#[test]
+fn git_log_arg_output_that_isnt_a_commit_record_is_an_error() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo", "Fix issue47601", "some content");
+    let path = tmp.path().join("repo");
+
+    for arg in ["-U3", "--patch-with-stat", "--summary"] {
+        let out = qro(&["--log-only", &format!("--git-log-arg={arg}"), "issue47601", path.to_str().unwrap()]);
+
+        assert!(!out.status.success(), "{arg}: {}", stdout(&out));
+        assert!(stderr(&out).contains("unexpected output line"), "{arg}: {}", stderr(&out));
+    }
+}
+
+// --- Per-line match counts (--show-match-count) ---
+
+#[test]
+fn show_match_count_annotates_each_line() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "foo bar foo baz FOO\nfoo once\n").unwrap();
+
+    let out = qro(&["-c", "-i", "--show-match-count", "foo", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("  1:foo bar foo baz FOO (3 matches)\n"), "got: {text}");
+    assert!(text.contains("  2:foo once (1 match)\n"), "got: {text}");
+}
+
+// --- Results cache (--cached) ---
+
+/// Run qro with XDG_CACHE_HOME pointing at `cache_home`.
+fn qro_with_cache_home(cache_home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(args)
+        .env("XDG_CACHE_HOME", cache_home)
+        .output()
+        .expect("failed to run qro")
+}
+
+#[test]
+fn cached_reprints_results_while_tree_is_unchanged() {
+    let cache = tempfile::tempdir().unwrap();
+    let tree = tempfile::tempdir().unwrap();
+    let file = tree.path().join("a.txt");
+    fs::write(&file, "findme one\n").unwrap();
+    let args = ["--no-log", "--cached", "findme", tree.path().to_str().unwrap()];
+
+    let first = qro_with_cache_home(cache.path(), &args);
+    assert!(stdout(&first).contains("1:findme one"), "got: {}", stdout(&first));
+
+    // Same size and mtime: indistinguishable from an unchanged tree, so the
+    // stale line coming back shows the search didn't run again.
+    let mtime = fs::metadata(&file).unwrap().modified().unwrap();
+    fs::write(&file, "findme two\n").unwrap();
+    fs::File::options().write(true).open(&file).unwrap().set_modified(mtime).unwrap();
+    let second = qro_with_cache_home(cache.path(), &args);
+
+    assert_eq!(stdout(&second), stdout(&first));
+}
+
+#[test]
+fn cached_results_are_invalidated_by_changes() {
+    let cache = tempfile::tempdir().unwrap();
+    let tree = tempfile::tempdir().unwrap();
+    fs::write(tree.path().join("a.txt"), "findme one\n").unwrap();
+    let args = ["--no-log", "--cached", "findme", tree.path().to_str().unwrap()];
+    qro_with_cache_home(cache.path(), &args);
+
+    fs::write(tree.path().join("a.txt"), "findme again\n").unwrap();
+    let text = stdout(&qro_with_cache_home(cache.path(), &args));
+    assert!(text.contains("1:findme again"), "modified file should bust the cache: {text}");
+
+    fs::write(tree.path().join("b.txt"), "findme new\n").unwrap();
+    let text = stdout(&qro_with_cache_home(cache.path(), &args));
+    assert!(text.contains("b.txt"), "new file should bust the cache: {text}");
+}
+
+// --- Directory pruning for `dir/**` exclusions ---
+
+#[cfg(unix)]
+#[test]
+fn negated_dir_glob_prunes_the_directory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("target/deep")).unwrap();
+    fs::write(tmp.path().join("target/deep/out.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("keep.txt"), "findme\n").unwrap();
+    // Reading the directory fails unless it's pruned (as root it never
+    // fails, and the test passes trivially).
+    let target = tmp.path().join("target");
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o000)).unwrap();
+
+    for flag in [["-g", "!target/**"], ["-x", "target/**"]] {
+        let out = qro(&["--no-log", flag[0], flag[1], "findme", tmp.path().to_str().unwrap()]);
+
+        assert!(stdout(&out).contains("keep.txt"), "got: {}", stdout(&out));
+        assert_eq!(stderr(&out), "", "{flag:?} should prune target/");
+    }
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn dir_exclusion_only_prunes_the_top_level_directory() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("src")).unwrap();
+    fs::create_dir_all(tmp.path().join("crates/a/src")).unwrap();
+    fs::write(tmp.path().join("src/x.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("crates/a/src/y.txt"), "findme\n").unwrap();
+
+    for flag in [["-g", "!src/**"], ["-x", "src/**"]] {
+        let out = qro(&["--no-log", "-c", flag[0], flag[1], "findme", tmp.path().to_str().unwrap()]);
+        let text = stdout(&out);
+
+        assert!(!text.contains("x.txt"), "{flag:?}: {text}");
+        assert!(text.contains("crates/a/src/y.txt"), "{flag:?} should keep nested src/: {text}");
+    }
+}
+
+// --- Reflog and stash search (--log-reflog, --log-stash) ---
+
+#[test]
+fn log_stash_finds_stash_message() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "proj", "initial", "one\n");
+    fs::write(repo.join("file.txt"), "two\n").unwrap();
+    git_in(&repo, &["stash", "push", "-q", "-m", "wip stash48101"]);
+    let path = repo.to_str().unwrap();
+
+    let out = qro(&["--log-only", "stash48101", path]);
+    assert!(!stdout(&out).contains("stash48101"), "stashes need --log-stash: {}", stdout(&out));
+
+    let out = qro(&["--log-only", "--log-stash", "stash48101", path]);
+    let text = stdout(&out);
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("wip stash48101 (stash)"), "got: {text}");
+}
+
+#[test]
+fn log_reflog_finds_reflog_entry() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "proj", "initial", "one\n");
+    git_in(&repo, &["checkout", "-q", "-b", "reflog48102"]);
+    let path = repo.to_str().unwrap();
+
+    let out = qro(&["--log-only", "--log-reflog", "reflog48102", path]);
+    let text = stdout(&out);
+
+    assert!(out.status.success(), "stderr: {}", stderr(&out));
+    assert!(text.contains("to reflog48102 (reflog)"), "got: {text}");
+}
+
+// --- Name matches without content matches (--name-only-unmatched) ---
+
+#[test]
+fn name_only_unmatched_drops_files_with_content_matches() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("parser.rs"), "fn main() {}\n").unwrap();
+    fs::write(tmp.path().join("parser_test.rs"), "use parser;\n").unwrap();
+    fs::write(tmp.path().join("lib.rs"), "mod parser;\n").unwrap();
+
+    let out = qro(&["--name-only-unmatched", "parser", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(text.contains("parser.rs\n  (name match)\n"), "got: {text}");
+    assert!(!text.contains("parser_test.rs"), "name and content match: {text}");
+    assert!(!text.contains("lib.rs"), "content match only: {text}");
+}
+
+// --- Display limit (--max-results-per-file) ---
+
+#[test]
+fn max_results_per_file_caps_display_but_not_count() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "findme 1\nfindme 2\nfindme 3\nfindme 4\nfindme 5\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["-c", "--max-results-per-file", "2", "findme", path]);
+    let text = stdout(&out);
+    assert!(text.ends_with("a.txt\n  1:findme 1\n  2:findme 2\n  ... (3 more)\n"), "got: {text}");
+
+    let out = qro(&["-c", "--summary", "--max-results-per-file", "2", "findme", path]);
+    assert!(stdout(&out).ends_with("a.txt (5 content)\n"), "got: {}", stdout(&out));
+}
+
+// --- Carriage returns (--strip-cr, --keep-cr) ---
+
+#[test]
+fn crlf_lines_print_without_carriage_returns() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("dos.txt"), "line findme\r\nprogress\rfindme\r\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    for extra in [&[][..], &["--slurp"]] {
+        let mut args = vec!["-c", "findme$", path];
+        args.extend(extra);
+        let text = stdout(&qro(&args));
+
+        assert!(text.contains("  1:line findme\n"), "{extra:?}: {text:?}");
+        assert!(text.contains("  2:progressfindme\n"), "{extra:?}: {text:?}");
+        assert!(!text.contains('\r'), "{extra:?}: {text:?}");
+    }
+
+    let text = stdout(&qro(&["-c", "--keep-cr", "findme", path]));
+    assert!(text.contains("  2:progress\rfindme\r\n"), "got: {text:?}");
+}
+
+// --- Hex dumps of binary matches (--binary-hex) ---
+
+#[test]
+fn binary_hex_dumps_bytes_around_match() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut bytes = vec![0u8; 40];
+    bytes[18..23].copy_from_slice(b"MAGIC");
+    fs::write(tmp.path().join("blob.bin"), &bytes).unwrap();
+
+    let out = qro(&["-c", "--binary-hex", "MAGIC", tmp.path().to_str().unwrap()]);
+    let text = stdout(&out);
+
+    assert!(
+        text.ends_with(
+            "blob.bin\n  byte 18 (0x12):\n\
+             \x20   00000000: 0000 0000 0000 0000 0000 0000 0000 0000  ................\n\
+             \x20   00000010: 0000 4d41 4749 4300 0000 0000 0000 0000  ..MAGIC.........\n\
+             \x20   00000020: 0000 0000 0000 0000                      ........\n"
+        ),
+        "got: {text}"
+    );
+}
+
+// --- Whole-line glob matching (--line-glob) ---
+
+#[test]
+fn line_glob_matches_whole_lines() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "foo123bar\nxfoo123bar\nFOO/BAR\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["-F", "foo*bar", path]);
+    assert!(!stdout(&out).contains("foo123bar"), "got: {}", stdout(&out));
+
+    let out = qro(&["--line-glob", "foo*bar", path]);
+    let text = stdout(&out);
+    assert!(text.contains("  1:foo123bar\n"), "got: {text}");
+    assert!(!text.contains("xfoo123bar"), "the glob is anchored to the whole line: {text}");
+    assert!(!text.contains("FOO/BAR"), "case-sensitive without -i: {text}");
+
+    let out = qro(&["--line-glob", "-i", "foo*bar", path]);
+    assert!(stdout(&out).contains("  3:FOO/BAR\n"), "got: {}", stdout(&out));
+}
+
+// --- Strict error handling (--fail-fast-on-error) ---
+
+#[cfg(unix)]
+#[test]
+fn fail_fast_on_error_stops_at_unreadable_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("found.txt"), "findme\n").unwrap();
+    // Can't be opened, even by root.
+    std::os::unix::fs::symlink(tmp.path().join("missing"), tmp.path().join("dangling")).unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "findme", path]);
+    assert!(out.status.success(), "errors are skipped by default");
+    assert!(stdout(&out).contains("found.txt"), "got: {}", stdout(&out));
+    assert!(stderr(&out).contains("dangling"), "got: {}", stderr(&out));
+
+    let out = qro(&["--no-log", "--fail-fast-on-error", "findme", path]);
+    assert!(!out.status.success());
+    assert_eq!(stdout(&out), "", "no results after an error");
+    assert!(stderr(&out).contains("dangling"), "got: {}", stderr(&out));
+}
+
+// --- Trailing whitespace (--trim-trailing) ---
+
+#[test]
+fn trim_trailing_strips_spaces_and_tabs() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "  findme  \t \n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let text = stdout(&qro(&["-c", "findme", path]));
+    assert!(text.contains("  1:  findme  \t \n"), "got: {text:?}");
+
+    let text = stdout(&qro(&["-c", "--trim-trailing", "findme", path]));
+    assert!(text.contains("  1:  findme\n"), "got: {text:?}");
+}
+
+// --- Sorting by last commit (--sort git-modified) ---
+
+#[test]
+fn sort_git_modified_lists_newest_commit_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(tmp.path(), "proj", "initial", "");
+    let commit_file = |name: &str, date: &str| {
+        fs::write(repo.join(name), "findme\n").unwrap();
+        git_in(&repo, &["add", name]);
+        git_in_with_env(&repo, &["commit", "-q", "-m", name], &[("GIT_COMMITTER_DATE", date)]);
+    };
+    commit_file("a.txt", "2020-01-01T00:00:00Z");
+    commit_file("b.txt", "2023-01-01T00:00:00Z");
+    commit_file("c.txt", "2021-01-01T00:00:00Z");
+    fs::write(repo.join("0-untracked.txt"), "findme\n").unwrap();
+    let path = repo.to_str().unwrap();
+
+    let out = qro(&["-c", "--summary", "--sort", "git-modified", "findme", path]);
+    let text = stdout(&out);
+    let files: Vec<&str> = text.lines().map(|l| l.rsplit('/').next().unwrap()).collect();
+
+    assert_eq!(
+        files,
+        ["b.txt (1 content)", "c.txt (1 content)", "a.txt (1 content)", "0-untracked.txt (1 content)"],
+        "got: {text}"
+    );
+}
+
+// --- Previews of name matches (--preview) ---
+
+#[test]
+fn preview_shows_first_matching_line_under_name_match() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("widget.rs"), "use std::io;\n// no match here\n").unwrap();
+    fs::write(tmp.path().join("widget_test.rs"), "mod tests;\nfn widget_works() {}\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let text = stdout(&qro(&["--no-log", "--preview", "widget", path]));
+    assert!(text.contains("widget.rs\n  (name match)\n  preview: 1:use std::io;\n"), "got: {text}");
+    // Already shows its content match, so no preview.
+    assert!(text.ends_with("widget_test.rs\n  (name match)\n  2:fn widget_works() {}\n"), "got: {text}");
+    assert_eq!(text.matches("preview:").count(), 1, "got: {text}");
+
+    let text = stdout(&qro(&["-n", "--preview", "widget", path]));
+    assert!(text.contains("widget_test.rs\n  preview: 2:fn widget_works() {}\n"), "got: {text}");
+}
+
+// --- Custom ignore files (--exclude-file) ---
+
+#[test]
+fn exclude_file_supports_negation() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tree = tmp.path().join("tree");
+    fs::create_dir(&tree).unwrap();
+    for name in ["app.log", "keep.log", "notes.txt"] {
+        fs::write(tree.join(name), "findme\n").unwrap();
+    }
+    let exclude = tmp.path().join("exclude");
+    fs::write(&exclude, "*.log\n!keep.log\n").unwrap();
+
+    for extra in [&[][..], &["--no-ignore"]] {
+        let mut args = vec!["-c", "--exclude-file", exclude.to_str().unwrap(), "findme"];
+        args.extend(extra);
+        args.push(tree.to_str().unwrap());
+        let text = stdout(&qro(&args));
+
+        assert!(!text.contains("app.log"), "{extra:?}: {text}");
+        assert!(text.contains("keep.log"), "{extra:?}: {text}");
+        assert!(text.contains("notes.txt"), "{extra:?}: {text}");
+    }
+}
+
+#[test]
+fn missing_exclude_file_is_an_error() {
+    let out = qro(&["--exclude-file", "no/such/file", "x", "tests/fixtures"]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("--exclude-file no/such/file"), "got: {}", stderr(&out));
+}
+
+// --- Per-directory counts (--count-by-dir) ---
+
+#[test]
+fn count_by_dir_totals_matching_lines_per_directory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("core/parse")).unwrap();
+    fs::create_dir_all(root.join("ui")).unwrap();
+    fs::write(root.join("core/lib.rs"), "TODO a\nTODO b\n").unwrap();
+    fs::write(root.join("core/parse/lexer.rs"), "TODO c\nok\nTODO d\n").unwrap();
+    fs::write(root.join("ui/view.rs"), "TODO e\n").unwrap();
+    fs::write(root.join("main.rs"), "TODO f\n").unwrap();
+    let path = root.to_str().unwrap();
+
+    let out = qro(&["--count-by-dir", "TODO", path]);
+    assert_eq!(
+        stdout(&out),
+        format!("{path}/: 1\n{path}/core/: 4\n{path}/ui/: 1\n"),
+    );
+
+    let out = qro(&["--count-by-dir=2", "TODO", path]);
+    assert_eq!(
+        stdout(&out),
+        format!("{path}/: 1\n{path}/core/: 2\n{path}/core/parse/: 2\n{path}/ui/: 1\n"),
+    );
+}
+
+// --- Patterns from a file or stdin (--patterns-from) ---
+
+/// Run qro with `input` on stdin.
+fn qro_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_qro"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run qro");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn patterns_from_stdin_match_any_pattern() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "alpha one\nbeta two\ngamma three\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    for input in [&b"alpha\nbeta\n"[..], b"alpha\0beta"] {
+        let out = qro_with_stdin(&["-c", "--patterns-from", "-", path], input);
+        let text = stdout(&out);
+
+        assert!(out.status.success(), "stderr: {}", stderr(&out));
+        assert!(text.contains("1:alpha one"), "got: {text}");
+        assert!(text.contains("2:beta two"), "got: {text}");
+        assert!(!text.contains("gamma"), "got: {text}");
+    }
+}
+
+#[test]
+fn patterns_from_rejects_a_second_positional() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("pats"), "findme\n").unwrap();
+    let pats = tmp.path().join("pats");
+
+    let out = qro(&["--patterns-from", pats.to_str().unwrap(), "findme", "."]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("cannot be used with"), "{}", stderr(&out));
+}
+
+// --- Restricting search to git repos (--repos-only) ---
+
+#[test]
+fn repos_only_skips_files_outside_repos() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "proj", "initial", "findme\n");
+    fs::write(tmp.path().join("loose.txt"), "findme\n").unwrap();
+    fs::create_dir(tmp.path().join("junk")).unwrap();
+    fs::write(tmp.path().join("junk/old.txt"), "findme\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "--repos-only", "findme", path]);
+    let text = stdout(&out);
+    assert!(text.contains("proj/file.txt"), "got: {text}");
+    assert!(!text.contains("loose.txt"), "got: {text}");
+    assert!(!text.contains("old.txt"), "got: {text}");
+
+    // Inside a repo, everything is in it.
+    let out = qro(&["--no-log", "--repos-only", "findme", tmp.path().join("proj").to_str().unwrap()]);
+    assert!(stdout(&out).contains("file.txt"), "got: {}", stdout(&out));
+}
+
+// --- Duplicate files (--find-duplicates) ---
+
+#[test]
+fn find_duplicates_groups_identical_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("a.txt"), "findme\nsame\n").unwrap();
+    fs::write(tmp.path().join("sub/copy.txt"), "findme\nsame\n").unwrap();
+    // Same size, different bytes.
+    fs::write(tmp.path().join("b.txt"), "findme\nelse\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "--find-duplicates", "findme", path]);
+
+    assert_eq!(
+        stdout(&out),
+        format!("2 identical files, 12 bytes each:\n  {path}/a.txt\n  {path}/sub/copy.txt\n")
+    );
+}
+
+#[test]
+fn find_duplicates_honours_print_files_to_stats_and_print_empty() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("b.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("c.txt"), "findme!\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+    let list = tmp.path().join("list");
+
+    let out = qro(&["--no-log", "--find-duplicates", "--print-files-to", list.to_str().unwrap(), "--stats", "findme", path]);
+    assert!(stdout(&out).contains("stats:\n  files matched: 2\n"), "{}", stdout(&out));
+    assert_eq!(fs::read_to_string(&list).unwrap(), format!("{path}/a.txt\0{path}/b.txt\0"));
+
+    let out = qro(&["--no-log", "--find-duplicates", "--print-empty", "findme!", path]);
+    assert_eq!(stdout(&out), "(no matches)\n");
+}
+
+// --- Context lines (-A, -B, -C) ---
+
+#[test]
+fn context_lines_merge_overlaps_and_separate_groups() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("t.txt"),
+        "a\nfindme 1\nb\nc\nd\ne\nf\nfindme 2\ng\nfindme 3\nh\n",
+    )
+    .unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "-c", "-C", "1", "findme", path]);
+
+    // Line 9 is in both windows of the last two matches, but shown once.
+    assert_eq!(
+        stdout(&out),
+        format!(
+            "{path}/t.txt\n  1-a\n  2:findme 1\n  3-b\n  --\n  \
+             7-f\n  8:findme 2\n  9-g\n  10:findme 3\n  11-h\n"
+        )
+    );
+}
+
+#[test]
+fn after_context_takes_precedence_over_context() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "a\nb\nfindme\nc\nd\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "-c", "--no-heading", "-C", "2", "-A", "0", "findme", path]);
+
+    // Context lines are joined with `-`, as in ripgrep.
+    assert_eq!(stdout(&out), format!("{path}/t.txt-1-a\n{path}/t.txt-2-b\n{path}/t.txt:3:findme\n"));
+}
+
+#[test]
+fn context_of_matches_past_the_display_limit_is_hidden() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "a\nfindme\nb\nc\nd\ne\nf\ng\nh\nfindme\ni\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "-c", "-C", "1", "--max-results-per-file", "1", "findme", path]);
+
+    assert_eq!(
+        stdout(&out),
+        format!("{path}/t.txt\n  1-a\n  2:findme\n  3-b\n  ... (1 more)\n")
+    );
+}
+
+#[test]
+fn context_of_filtered_out_matches_is_dropped() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "a\nfindme keep\nb\nc\nd\nfindme drop\ne\n").unwrap();
+    fs::write(tmp.path().join("u.txt"), "x\nfindme drop\ny\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "-c", "-C", "1", "-V", "drop", "findme", path]);
+
+    assert_eq!(stdout(&out), format!("{path}/t.txt\n  1-a\n  2:findme keep\n  3-b\n"));
+}
+
+// --- Bare repositories (--git-dir) ---
+
+#[test]
+fn git_dir_searches_bare_repo_log() {
+    let tmp = tempfile::tempdir().unwrap();
+    let work = tempfile::tempdir().unwrap();
+    let repo = make_git_repo(work.path(), "repo", "Fix issue99301 in mirror", "some content");
+    let bare = tmp.path().join("mirror.git");
+    git_in(work.path(), &["clone", "--bare", repo.to_str().unwrap(), bare.to_str().unwrap()]);
+    let search_dir = tempfile::tempdir().unwrap();
+
+    let out = qro(&[
+        "--log-only",
+        "--git-dir",
+        bare.to_str().unwrap(),
+        "issue99301",
+        search_dir.path().to_str().unwrap(),
+    ]);
+    let text = stdout(&out);
+
+    assert!(out.status.success());
+    assert!(
+        text.starts_with(&format!("{} (git log):\n", bare.display())),
+        "should label the block with the bare repo path: {text}"
+    );
+    assert!(text.contains("Fix issue99301 in mirror"));
+}
+
+#[test]
+fn git_dir_rejects_non_repo() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--log-only", "--git-dir", path, "pattern", path]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("not a git repository"), "{}", stderr(&out));
+}
+
+// --- ripgrep-style JSON output (--json) ---
+
+#[test]
+fn json_golden_output() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "before\nsay \"findme\" findme\n").unwrap();
+    // Name matches and commits are left out.
+    fs::write(tmp.path().join("findme.txt"), "nothing here\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--json", "-B", "1", "findme", path]);
+
+    assert!(out.status.success());
+    assert_eq!(
+        stdout(&out),
+        format!(
+            "{{\"type\":\"qro\",\"data\":{{\"schema_version\":1}}}}\n\
+             {{\"type\":\"begin\",\"data\":{{\"path\":{{\"text\":\"{path}/t.txt\"}}}}}}\n\
+             {{\"type\":\"context\",\"data\":{{\"path\":{{\"text\":\"{path}/t.txt\"}},\
+             \"lines\":{{\"text\":\"before\\n\"}},\"line_number\":1,\"absolute_offset\":0,\
+             \"submatches\":[]}}}}\n\
+             {{\"type\":\"match\",\"data\":{{\"path\":{{\"text\":\"{path}/t.txt\"}},\
+             \"lines\":{{\"text\":\"say \\\"findme\\\" findme\\n\"}},\"line_number\":2,\
+             \"absolute_offset\":7,\"submatches\":[\
+             {{\"match\":{{\"text\":\"findme\"}},\"start\":5,\"end\":11}},\
+             {{\"match\":{{\"text\":\"findme\"}},\"start\":13,\"end\":19}}]}}}}\n\
+             {{\"type\":\"end\",\"data\":{{\"path\":{{\"text\":\"{path}/t.txt\"}},\
+             \"binary_offset\":null,\"stats\":{{\"matched_lines\":1,\"matches\":2}}}}}}\n"
+        )
+    );
+}
+
+#[test]
+fn json_keeps_a_missing_final_newline_missing() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "findme\nfindme").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--json", "findme", path]);
+    let text = stdout(&out);
+
+    assert!(text.contains("\"lines\":{\"text\":\"findme\\n\"},\"line_number\":1"), "got: {text}");
+    assert!(text.contains("\"lines\":{\"text\":\"findme\"},\"line_number\":2"), "got: {text}");
+}
+
+#[test]
+fn json_keeps_carriage_returns_like_ripgrep() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("t.txt"), "say findme\r\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--json", "findme", path]);
+    let text = stdout(&out);
+
+    assert!(text.contains("\"lines\":{\"text\":\"say findme\\r\\n\"}"), "got: {text}");
+    assert!(text.contains("\"start\":4,\"end\":10"), "got: {text}");
+}
+
+#[test]
+fn json_conflicts_with_names_only() {
+    let out = qro(&["--json", "-n", "pattern", "tests/fixtures/"]);
+
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("cannot be used with"), "{}", stderr(&out));
+}
+
+// --- Parallel content search (--threads) ---
+
+#[test]
+fn threads_do_not_change_output() {
+    let tmp = tempfile::tempdir().unwrap();
+    for dir in ["a", "b/c", "d"] {
+        fs::create_dir_all(tmp.path().join(dir)).unwrap();
+        for i in 0..20 {
+            fs::write(
+                tmp.path().join(dir).join(format!("f{i}.txt")),
+                format!("x\nfindme {i}\ny\nfindme again\n"),
+            )
+            .unwrap();
+        }
+    }
+    let path = tmp.path().to_str().unwrap();
+
+    let sequential = qro(&["--no-log", "-j", "1", "-C", "1", "findme", path]);
+    let parallel = qro(&["--no-log", "-j", "8", "-C", "1", "findme", path]);
+
+    assert!(sequential.status.success());
+    assert_eq!(stdout(&parallel), stdout(&sequential));
+    assert_eq!(stdout(&sequential).matches("findme again").count(), 60);
+}
+
+// --- Per-file counts (--count, --count-matches) ---
+
+#[test]
+fn count_prints_matching_lines_per_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    make_git_repo(tmp.path(), "repo", "findme in a commit", "findme\nnothing\n");
+    fs::write(tmp.path().join("a.txt"), "findme findme\nfindme\n").unwrap();
+    fs::write(tmp.path().join("findme.txt"), "no match inside\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--count", "findme", path]);
+
+    // Names, commits and files without matches aren't listed.
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), format!("{path}/a.txt:2\n{path}/repo/file.txt:1\n"));
+
+    let out = qro(&["--count-matches", "findme", path]);
+    assert_eq!(stdout(&out), format!("{path}/a.txt:3\n{path}/repo/file.txt:1\n"));
+}
+
+#[test]
+fn count_modes_honour_print_files_to_and_stats() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "findme\n").unwrap();
+    fs::write(tmp.path().join("b.txt"), "nothing\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+    let list = tmp.path().join("list");
+
+    for mode in ["--count", "--count-matches", "--count-lines-total", "--count-by-dir", "--json"] {
+        let out = qro(&["--no-log", mode, "--print-files-to", list.to_str().unwrap(), "findme", path]);
+
+        assert!(out.status.success(), "{mode}: {}", stderr(&out));
+        assert_eq!(fs::read_to_string(&list).unwrap(), format!("{path}/a.txt\0"), "{mode}");
+    }
+
+    let out = qro(&["--no-log", "--count", "--stats", "findme", path]);
+    let text = stdout(&out);
+    assert!(text.starts_with(&format!("{path}/a.txt:1\n\nstats:\n  files matched: 1\n")), "{text}");
+
+    let out = qro(&["--json", "--stats", "findme", path]);
+    assert!(!out.status.success(), "--stats would corrupt the JSON stream");
+}
+
+#[test]
+fn count_without_matches_prints_nothing_and_succeeds() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "nothing\n").unwrap();
+
+    let out = qro(&["--no-log", "--count", "findme", tmp.path().to_str().unwrap()]);
+
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "");
+}
+
+// --- Zero-byte files (--only-empty, --skip-empty) ---
+
+#[test]
+fn only_empty_keeps_zero_byte_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("marker.keep"), "").unwrap();
+    fs::write(tmp.path().join("notes.keep"), "keep\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "--only-empty", "keep", path]);
+
+    assert_eq!(stdout(&out), format!("{path}/marker.keep\n  (name match)\n"));
+}
+
+#[test]
+fn skip_empty_drops_zero_byte_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("marker.keep"), "").unwrap();
+    fs::write(tmp.path().join("notes.keep"), "nothing\n").unwrap();
+    let path = tmp.path().to_str().unwrap();
+
+    let out = qro(&["--no-log", "-n", "--skip-empty", "keep", path]);
+
+    assert_eq!(stdout(&out), format!("{path}/notes.keep\n"));
+}